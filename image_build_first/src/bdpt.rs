@@ -0,0 +1,427 @@
+//! # `bdpt.rs` 模块说明
+//!
+//! 双向路径追踪（Bidirectional Path Tracing, Veach 1997）。
+//!
+//! 每个相机样本分别从相机和光源出发生成两条子路径，再把相机子路径的前 t 个顶点与
+//! 光源子路径的前 s 个顶点连接成完整路径，所有 (s, t) 组合的贡献用 MIS（balance heuristic）加权求和。
+//!
+//! - 光源子路径的起点由 `lights` 的 `sample_surface` 给出，发射方向按余弦分布采样，
+//!   辐亮度取自光源表面的材质，因此 `lights` 列表里的物体需要带上真正的发光材质；
+//!   `Camera::render` 传入的是 `Camera::emitters`（未设置时为相机的光源列表）
+//! - 顶点处的散射复用 `Material` 的 `scatter`、`scattering_pdf` 与 `scattering_value`；
+//!   只返回 `skip_pdf_ray` 的材质（玻璃、镜面金属）视为 delta 顶点，不参与连接；
+//!   标记为 `null_collision` 的空碰撞（介质、薄片透射）只累乘权重，不产生顶点
+//! - 光源子路径上的散射与连接用 `adjoint_scattering_value` 计算，带着色法线的伴随修正
+//! - 不实现 t = 1（光源子路径直接连到相机镜头）的策略，MIS 权重中也相应去掉这一项
+//! - 射线逃逸到背景（含天空）只能由相机子路径得到，权重为 1
+
+use crate::camera::Camera;
+use crate::hittable::{HitRecord, Hittable};
+use crate::integrator::{
+    adjoint_scattering_value, emission_pdf, emitted_toward, reoriented, sample_emission, visibility,
+};
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::rtweekend;
+use crate::vec3::{Color, Point3, Vec3};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum VertexKind {
+    Camera,
+    Light,
+    Surface,
+    Medium,
+}
+
+#[derive(Clone)]
+struct Vertex<'a> {
+    kind: VertexKind,
+    p: Point3,
+    n: Vec3, //  几何法线，相机与介质顶点为零向量
+    rec: Option<HitRecord<'a>>,
+    r_in: Ray, //  到达该顶点的射线
+    beta: Color,
+    pdf_fwd: f64, //  沿子路径生成方向得到该顶点的面积概率密度
+    pdf_rev: f64, //  沿相反方向得到该顶点的面积概率密度
+    delta: bool,
+}
+
+impl<'a> Vertex<'a> {
+    fn camera(r: &Ray) -> Self {
+        Self {
+            kind: VertexKind::Camera,
+            p: *r.origin(),
+            n: Vec3::new(0.0, 0.0, 0.0),
+            rec: None,
+            r_in: *r,
+            beta: Color::new(1.0, 1.0, 1.0),
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+            delta: false,
+        }
+    }
+
+    fn on_surface(&self) -> bool {
+        self.kind == VertexKind::Surface || self.kind == VertexKind::Light
+    }
+
+    //  把从该顶点出发的立体角概率密度换算成 `next` 处的面积概率密度
+    fn convert_density(&self, pdf: f64, next: &Vertex) -> f64 {
+        let w = next.p - self.p;
+        let dist_squared = w.length_squared();
+        if dist_squared == 0.0 {
+            return 0.0;
+        }
+        let mut pdf = pdf / dist_squared;
+        if next.on_surface() {
+            pdf *= Vec3::dot(&next.n, &w).abs() / dist_squared.sqrt();
+        }
+        pdf
+    }
+
+    //  该顶点朝 `next` 方向散射的 f·cosθ；光源子路径上的顶点带伴随修正
+    fn f(&self, next: &Vertex, from_light: bool) -> Color {
+        match &self.rec {
+            Some(rec) => {
+                let scattered = Ray::new(self.p, next.p - self.p, self.r_in.time());
+                if from_light {
+                    adjoint_scattering_value(&self.r_in, rec, &scattered)
+                } else {
+                    rec.mat.scattering_value(&self.r_in, rec, &scattered)
+                }
+            }
+            None => Color::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    //  从 `prev` 到达该顶点后散射到 `next` 的面积概率密度；光源顶点按余弦发射计算
+    fn pdf(&self, prev: Option<&Vertex>, next: &Vertex) -> f64 {
+        match self.kind {
+            VertexKind::Camera => 0.0,
+            VertexKind::Light => self.pdf_emission(next),
+            VertexKind::Surface | VertexKind::Medium => {
+                let (Some(prev), Some(rec)) = (prev, self.rec.as_ref()) else {
+                    return 0.0;
+                };
+                let time = self.r_in.time();
+                let r_in = Ray::new(prev.p, self.p - prev.p, time);
+                let scattered = Ray::new(self.p, next.p - self.p, time);
+                let pdf = rec
+                    .mat
+                    .scattering_pdf(&r_in, &reoriented(rec, &r_in), &scattered);
+                self.convert_density(pdf, next)
+            }
+        }
+    }
+
+    //  把该顶点当作余弦发射的面光源时，发射到 `next` 的面积概率密度
+    fn pdf_emission(&self, next: &Vertex) -> f64 {
+        let w = next.p - self.p;
+        let pdf_dir = match self.rec.as_ref() {
+            Some(rec) => emission_pdf(rec, &w),
+            None => Vec3::dot(&self.n, &Vec3::unit_vector(&w)).abs() / rtweekend::PI_F64,
+        };
+        self.convert_density(pdf_dir, next)
+    }
+}
+
+/// 双向路径追踪估计一条相机射线带回的辐亮度，`hit` 是相机射线在 `world` 中的第一个交点
+pub fn li<'a>(
+    cam: &Camera,
+    r: &Ray,
+    hit: Option<HitRecord<'a>>,
+    world: &'a dyn Hittable,
+    lights: &'a dyn Hittable,
+) -> Color {
+    let max_depth = cam.max_depth;
+    let mut camera_path = vec![Vertex::camera(r)];
+    let escaped = random_walk(
+        world,
+        (*r, hit),
+        Color::new(1.0, 1.0, 1.0),
+        1.0,
+        max_depth,
+        false,
+        &mut camera_path,
+    );
+    let light_path = light_subpath(world, lights, r.time(), max_depth);
+
+    let mut l = Color::new(0.0, 0.0, 0.0);
+    if let Some((ray, beta)) = escaped {
+        l += beta * cam.background_color(&ray);
+    }
+
+    for t in 2..=camera_path.len() {
+        for s in 0..=light_path.len() {
+            if s + t - 2 > max_depth {
+                continue;
+            }
+            l += connect(world, lights, &camera_path, &light_path, s, t);
+        }
+    }
+    l
+}
+
+//  沿射线在场景中随机游走，把新顶点追加到 `path`；射线逃逸时返回逃逸射线及此时的吞吐量。
+//  出发时给出射线及其第一个交点，`from_light` 表示这是光源子路径
+fn random_walk<'a>(
+    world: &'a dyn Hittable,
+    (mut r, mut hit): (Ray, Option<HitRecord<'a>>),
+    mut beta: Color,
+    mut pdf_fwd: f64,
+    max_depth: usize,
+    from_light: bool,
+    path: &mut Vec<Vertex<'a>>,
+) -> Option<(Ray, Color)> {
+    let mut bounces = 0;
+    while bounces < max_depth {
+        let rec = match hit.take() {
+            Some(rec) => rec,
+            None => return Some((r, beta * r.medium_transmittance(f64::INFINITY))),
+        };
+        beta = beta * r.medium_transmittance(rec.t);
+
+        //  空碰撞只改变权重，不作为路径顶点，这样采样得到的边与连接边一样都以透射率为期望
+        let srec = rec.mat.scatter(&r, &rec);
+        if let Some(srec) = srec.as_ref().filter(|srec| srec.null_collision) {
+            beta = beta * srec.attenuation;
+            r = r.scattered(rec.p, *r.direction());
+            hit = world.hit(&r, &Interval::new(0.001, f64::INFINITY));
+            bounces += 1;
+            continue;
+        }
+
+        let kind = if rec.mat.is_volumetric() {
+            VertexKind::Medium
+        } else {
+            VertexKind::Surface
+        };
+        let mut vertex = Vertex {
+            kind,
+            p: rec.p,
+            n: if kind == VertexKind::Medium {
+                Vec3::new(0.0, 0.0, 0.0)
+            } else {
+                rec.normal
+            },
+            rec: Some(rec.clone()),
+            r_in: r,
+            beta,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+            delta: false,
+        };
+        let prev_idx = path.len() - 1;
+        vertex.pdf_fwd = path[prev_idx].convert_density(pdf_fwd, &vertex);
+        path.push(vertex);
+        let idx = prev_idx + 1;
+        bounces += 1;
+        if bounces >= max_depth {
+            break;
+        }
+
+        let Some(srec) = srec else {
+            break;
+        };
+        let pdf_rev;
+        if let Some(pdf) = srec.pdf_ptr.as_ref() {
+            let direction = pdf.generate();
+            pdf_fwd = pdf.value(&direction);
+            if pdf_fwd <= 0.0 {
+                break;
+            }
+            let scattered = r.scattered(rec.p, direction);
+            let value = if from_light {
+                adjoint_scattering_value(&r, &rec, &scattered)
+            } else {
+                rec.mat.scattering_value(&r, &rec, &scattered)
+            };
+            beta = beta * value / pdf_fwd;
+
+            //  反方向：从新方向射入、散射回上一个顶点
+            let reversed = Ray::new(rec.p + direction, -direction, r.time());
+            let back = Ray::new(rec.p, -*r.direction(), r.time());
+            pdf_rev = rec
+                .mat
+                .scattering_pdf(&reversed, &reoriented(&rec, &reversed), &back);
+            r = scattered;
+        } else if let Some(pdf_ray) = srec.skip_pdf_ray {
+            path[idx].delta = true;
+            beta = beta * srec.attenuation;
+            pdf_fwd = 0.0;
+            pdf_rev = 0.0;
+            r = pdf_ray;
+        } else {
+            break;
+        }
+
+        let rev = path[idx].convert_density(pdf_rev, &path[idx - 1]);
+        path[idx - 1].pdf_rev = rev;
+
+        if beta.max_component() <= 0.0 {
+            break;
+        }
+        hit = world.hit(&r, &Interval::new(0.001, f64::INFINITY));
+    }
+    None
+}
+
+//  从光源表面出发的子路径，第一个顶点的 beta 记为 1 / pdf_pos，辐亮度在连接时再计算
+fn light_subpath<'a>(
+    world: &'a dyn Hittable,
+    lights: &'a dyn Hittable,
+    time: f64,
+    max_depth: usize,
+) -> Vec<Vertex<'a>> {
+    let Some((rec, pdf_pos)) = lights.sample_surface(time) else {
+        return Vec::new();
+    };
+    if pdf_pos <= 0.0 {
+        return Vec::new();
+    }
+
+    let (direction, pdf_dir) = sample_emission(&rec);
+    let le = emitted_toward(&rec, &(rec.p + direction), time);
+
+    let mut path = vec![Vertex {
+        kind: VertexKind::Light,
+        p: rec.p,
+        n: rec.normal,
+        rec: Some(rec.clone()),
+        r_in: Ray::new(rec.p, rec.normal, time),
+        beta: Color::new(1.0, 1.0, 1.0) / pdf_pos,
+        pdf_fwd: pdf_pos,
+        pdf_rev: 0.0,
+        delta: false,
+    }];
+    if pdf_dir <= 0.0 || le.max_component() <= 0.0 {
+        return path;
+    }
+
+    //  le·cosθ / (pdf_pos·pdf_dir)
+    let cos_light = Vec3::dot(&Vec3::unit_vector(&direction), &rec.normal).abs();
+    let beta = le * cos_light / (pdf_pos * pdf_dir);
+    let r = Ray::new(rec.p, direction, time);
+    random_walk(
+        world,
+        (r, world.hit(&r, &Interval::new(0.001, f64::INFINITY))),
+        beta,
+        pdf_dir,
+        max_depth.saturating_sub(1),
+        true,
+        &mut path,
+    );
+    path
+}
+
+//  使用光源子路径前 s 个顶点与相机子路径前 t 个顶点构成的路径贡献（已乘 MIS 权重）
+fn connect(
+    world: &dyn Hittable,
+    lights: &dyn Hittable,
+    camera_path: &[Vertex],
+    light_path: &[Vertex],
+    s: usize,
+    t: usize,
+) -> Color {
+    let black = Color::new(0.0, 0.0, 0.0);
+    let pt = &camera_path[t - 1];
+    let time = pt.r_in.time();
+
+    let l = if s == 0 {
+        //  相机子路径自己打到了发光表面
+        let Some(rec) = pt.rec.as_ref() else {
+            return black;
+        };
+        pt.beta * rec.mat.emitted(&pt.r_in, rec, rec.u, rec.v, &rec.p)
+    } else {
+        let qs = &light_path[s - 1];
+        if pt.delta || qs.delta {
+            return black;
+        }
+
+        let d = pt.p - qs.p;
+        let dist_squared = d.length_squared();
+        if dist_squared == 0.0 {
+            return black;
+        }
+        let contribution = if s == 1 {
+            let Some(rec) = qs.rec.as_ref() else {
+                return black;
+            };
+            let le = emitted_toward(rec, &pt.p, time);
+            let cos_light = Vec3::dot(&qs.n, &d).abs() / dist_squared.sqrt();
+            pt.beta * pt.f(qs, false) * le * qs.beta * cos_light / dist_squared
+        } else {
+            qs.beta * qs.f(pt, true) * pt.f(qs, false) * pt.beta / dist_squared
+        };
+        if contribution.max_component() <= 0.0 {
+            return black;
+        }
+        contribution * visibility(world, &qs.p, &pt.p, time, pt.r_in.media)
+    };
+
+    if l.max_component() <= 0.0 {
+        return black;
+    }
+    l * mis_weight(lights, camera_path, light_path, s, t)
+}
+
+//  balance heuristic：1 / (1 + Σ 其它策略与当前策略的概率密度之比)
+fn mis_weight(
+    lights: &dyn Hittable,
+    camera_path: &[Vertex],
+    light_path: &[Vertex],
+    s: usize,
+    t: usize,
+) -> f64 {
+    let mut cam_rev: Vec<f64> = camera_path[..t].iter().map(|v| v.pdf_rev).collect();
+    let mut light_rev: Vec<f64> = light_path[..s].iter().map(|v| v.pdf_rev).collect();
+
+    let pt = &camera_path[t - 1];
+    let pt_minus = &camera_path[t - 2];
+    if s > 0 {
+        let qs = &light_path[s - 1];
+        let qs_minus = if s >= 2 {
+            Some(&light_path[s - 2])
+        } else {
+            None
+        };
+        cam_rev[t - 1] = qs.pdf(qs_minus, pt);
+        cam_rev[t - 2] = pt.pdf(Some(qs), pt_minus);
+        light_rev[s - 1] = pt.pdf(Some(pt_minus), qs);
+        if let Some(qs_minus) = qs_minus {
+            light_rev[s - 2] = qs.pdf(Some(pt), qs_minus);
+        }
+    } else {
+        //  不在 lights 列表里的发光体无法由光源子路径生成，只有这一种策略
+        let pdf_pos = lights.surface_pdf(&pt.p, pt.r_in.time());
+        if pdf_pos <= 0.0 {
+            return 1.0;
+        }
+        cam_rev[t - 1] = pdf_pos;
+        cam_rev[t - 2] = pt.pdf_emission(pt_minus);
+    }
+
+    //  delta 顶点的概率密度记为 0，比值中按 1 处理
+    let remap = |f: f64| if f != 0.0 { f } else { 1.0 };
+
+    let mut sum_ri = 0.0;
+    let mut ri = 1.0;
+    for i in (2..t).rev() {
+        ri *= remap(cam_rev[i]) / remap(camera_path[i].pdf_fwd);
+        if !camera_path[i].delta && !camera_path[i - 1].delta {
+            sum_ri += ri;
+        }
+    }
+
+    ri = 1.0;
+    for i in (0..s).rev() {
+        ri *= remap(light_rev[i]) / remap(light_path[i].pdf_fwd);
+        let delta_light_vertex = i > 0 && light_path[i - 1].delta;
+        if !light_path[i].delta && !delta_light_vertex {
+            sum_ri += ri;
+        }
+    }
+
+    1.0 / (1.0 + sum_ri)
+}
@@ -0,0 +1,143 @@
+//! # `blend.rs` 模块说明
+//!
+//! 用纹理作遮罩在同一表面上混合两种材质，例如在金属上画出锈迹、在地板上画出污渍。
+//! 遮罩取纹理颜色的第一个分量 w ∈ [0, 1]：w = 0 处为第一种材质，w = 1 处为第二种。
+//!
+//! - 两种材质都只有可求值波瓣时，BSDF 与概率密度都按 (1 - w, w) 线性混合
+//! - 否则按 w 随机选择其中一种。BSDF 与概率密度按 (1 - w)·Pa、w·Pb 加权平均，
+//!   P 为各自的 `pdf_probability`，只由材质决定而不依赖这一次随机选中了哪一种，估计保持无偏
+//! - 自发光按 w 线性混合，着色法线按 w 混合后归一化
+//! - 合成用的 `Holdout`、`ShadowCatcher` 标记与参与介质一样，只有两种材质都是时才成立；两面发光只要有一种是即可
+
+use std::sync::Arc;
+
+use crate::hittable::HitRecord;
+use crate::material::{Material, ScatterRecord};
+use crate::pdf::WeightedMixturePdf;
+use crate::ray::Ray;
+use crate::rtweekend::random_double;
+use crate::texture::Texture;
+use crate::vec3::{Color, Point3, Vec3};
+
+#[derive(Debug)]
+pub struct Blend {
+    first: Arc<dyn Material>,
+    second: Arc<dyn Material>,
+    mask: Arc<dyn Texture>,
+}
+
+impl Blend {
+    pub fn new(first: Arc<dyn Material>, second: Arc<dyn Material>, mask: Arc<dyn Texture>) -> Self {
+        Self {
+            first,
+            second,
+            mask,
+        }
+    }
+
+    fn weight(&self, u: f64, v: f64, p: &Point3) -> f64 {
+        self.mask.value(u, v, p).x().clamp(0.0, 1.0)
+    }
+
+    //  两种材质各自给出概率密度的概率乘以混合权重
+    fn lobe_weights(&self, r_in: &Ray, rec: &HitRecord) -> (f64, f64) {
+        let w = self.weight(rec.u, rec.v, &rec.p);
+        let a = if w < 1.0 { (1.0 - w) * self.first.pdf_probability(r_in, rec) } else { 0.0 };
+        let b = if w > 0.0 { w * self.second.pdf_probability(r_in, rec) } else { 0.0 };
+        (a, b)
+    }
+}
+
+impl Material for Blend {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let w = self.weight(rec.u, rec.v, &rec.p);
+        if w <= 0.0 {
+            return self.first.scatter(r_in, rec);
+        }
+        if w >= 1.0 {
+            return self.second.scatter(r_in, rec);
+        }
+
+        //  两者都只有可求值波瓣：混合概率密度；否则随机选择一种
+        let pure = self.first.pdf_probability(r_in, rec) >= 1.0 && self.second.pdf_probability(r_in, rec) >= 1.0;
+        if pure {
+            let first = self.first.scatter(r_in, rec);
+            let second = self.second.scatter(r_in, rec);
+            if let (
+                Some(ScatterRecord { attenuation: a, pdf_ptr: Some(pdf_a), .. }),
+                Some(ScatterRecord { attenuation: b, pdf_ptr: Some(pdf_b), .. }),
+            ) = (&first, &second)
+            {
+                return Some(ScatterRecord {
+                    attenuation: (1.0 - w) * *a + w * *b,
+                    pdf_ptr: Some(Arc::new(WeightedMixturePdf::new(vec![
+                        (1.0 - w, pdf_a.clone()),
+                        (w, pdf_b.clone()),
+                    ]))),
+                    skip_pdf_ray: None,
+                    null_collision: false,
+                });
+            }
+            return first.or(second);
+        }
+
+        if random_double() < w {
+            self.second.scatter(r_in, rec)
+        } else {
+            self.first.scatter(r_in, rec)
+        }
+    }
+
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord, u: f64, v: f64, p: &Point3) -> Color {
+        let w = self.weight(u, v, p);
+        (1.0 - w) * self.first.emitted(r_in, rec, u, v, p) + w * self.second.emitted(r_in, rec, u, v, p)
+    }
+
+    fn pdf_probability(&self, r_in: &Ray, rec: &HitRecord) -> f64 {
+        let (a, b) = self.lobe_weights(r_in, rec);
+        a + b
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let (a, b) = self.lobe_weights(r_in, rec);
+        if a + b <= 0.0 {
+            return 0.0;
+        }
+        let pdf_a = if a > 0.0 { self.first.scattering_pdf(r_in, rec, scattered) } else { 0.0 };
+        let pdf_b = if b > 0.0 { self.second.scattering_pdf(r_in, rec, scattered) } else { 0.0 };
+        (a * pdf_a + b * pdf_b) / (a + b)
+    }
+
+    fn scattering_value(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        let (a, b) = self.lobe_weights(r_in, rec);
+        let black = Color::new(0.0, 0.0, 0.0);
+        if a + b <= 0.0 {
+            return black;
+        }
+        let value_a = if a > 0.0 { self.first.scattering_value(r_in, rec, scattered) } else { black };
+        let value_b = if b > 0.0 { self.second.scattering_value(r_in, rec, scattered) } else { black };
+        (a * value_a + b * value_b) / (a + b)
+    }
+
+    fn is_volumetric(&self) -> bool {
+        self.first.is_volumetric() && self.second.is_volumetric()
+    }
+
+    fn shading_normal(&self, rec: &HitRecord) -> Vec3 {
+        let w = self.weight(rec.u, rec.v, &rec.p);
+        let n = (1.0 - w) * self.first.shading_normal(rec) + w * self.second.shading_normal(rec);
+        if n.length_squared() > 0.0 { Vec3::unit_vector(&n) } else { rec.normal }
+    }
+
+    fn is_two_sided_emitter(&self) -> bool {
+        self.first.is_two_sided_emitter() || self.second.is_two_sided_emitter()
+    }
+
+    fn is_holdout(&self) -> bool {
+        self.first.is_holdout() && self.second.is_holdout()
+    }
+
+    fn is_shadow_catcher(&self) -> bool {
+        self.first.is_shadow_catcher() && self.second.is_shadow_catcher()
+    }
+}
@@ -4,10 +4,12 @@ use std::sync::Arc;
 //  use crate::rtweekend;
 use crate::interval::Interval;
 use crate::ray::Ray;
+use crate::vec3::Color;
 
 pub struct BvhNode {
     left: Arc<dyn Hittable + Send + Sync>,
     right: Arc<dyn Hittable + Send + Sync>,
+    leaves: bool, //  左右子节点是场景中的物体本身而不是子树，命中时以它们标识物体
     pub bbox: Aabb,
 }
 
@@ -62,7 +64,7 @@ impl BvhNode {
 
         bbox = Aabb::surrounding_box(&left.bounding_box(), &right.bounding_box());
 
-        Self { left, right, bbox }
+        Self { left, right, leaves: object_span <= 2, bbox }
     }
 
     fn box_compare(
@@ -110,11 +112,22 @@ impl Hittable for BvhNode {
             return None;
         }
 
-        let hit_left = self.left.hit(r, ray_t);
+        //  叶子节点的子节点是场景中的物体，把命中记录标记为该物体
+        let stamp = |rec: Option<HitRecord<'a>>, object: &Arc<dyn Hittable + Send + Sync>| match rec {
+            Some(rec) if self.leaves => Some(HitRecord { object: Arc::as_ptr(object) as *const () as usize, ..rec }),
+            rec => rec,
+        };
+
+        //  只有一个物体时左右子树是同一个对象，只求一次交，否则随机采样的介质等会被采样两次
+        let hit_left = stamp(self.left.hit(r, ray_t), &self.left);
+        if Arc::ptr_eq(&self.left, &self.right) {
+            return hit_left;
+        }
         let hit_right = match hit_left.as_ref() {
             Some(rec) => self.right.hit(r, &Interval::new(ray_t.min, rec.t)),
             None => self.right.hit(r, ray_t),
         };
+        let hit_right = stamp(hit_right, &self.right);
 
         match (hit_left, hit_right) {
             (Some(left_rec), Some(right_rec)) => {
@@ -133,4 +146,17 @@ impl Hittable for BvhNode {
     fn bounding_box(&self) -> Aabb {
         self.bbox.clone()
     }
+
+    fn transmittance(&self, r: &Ray, ray_t: &Interval) -> Color {
+        if !self.bbox.hit(r, ray_t) {
+            return Color::new(1.0, 1.0, 1.0);
+        }
+
+        //  只有一个物体时左右子树是同一个对象，不能重复计算
+        let left = self.left.transmittance(r, ray_t);
+        if Arc::ptr_eq(&self.left, &self.right) || left.max_component() <= 0.0 {
+            return left;
+        }
+        left * self.right.transmittance(r, ray_t)
+    }
 }
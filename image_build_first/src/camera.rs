@@ -1,9 +1,16 @@
-use crate::hittable::{Hittable};
+use crate::hittable::{HitRecord, Hittable};
 use crate::interval::Interval;
 use crate::ray::Ray;
 use crate::rtweekend::{self, random_double};
 use crate::vec3::{Color, Point3, Vec3};
 use crate::pdf::{HittablePdf, MixturePdf, Pdf};
+use crate::sky::Sky;
+use crate::integrator::Integrator;
+use crate::bdpt;
+use crate::photon_map::PhotonMaps;
+use crate::light_tracer;
+use crate::mlt;
+use crate::spectrum::{self, HERO_WAVELENGTHS};
 
 use std::sync::Condvar;
 use std::sync::atomic::AtomicUsize;
@@ -16,6 +23,19 @@ const HEIGHT_PARTITION: usize = 32;
 const WIDTH_PARTITION: usize = 32;
 const THREAD_LIMIT: usize = 24;
 
+//  RGBA 输出时相机射线第一次命中的情况
+enum Coverage {
+    Opaque,
+    //  没有击中物体，或者击中了 `Holdout`
+    Transparent,
+    //  击中 `ShadowCatcher`：分别估计完整场景与只有光源时它反射的光
+    ShadowCatcher,
+}
+
+fn luminance(c: &Color) -> f64 {
+    0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
+}
+
 #[derive(Clone)]
 pub struct Camera {
     pub aspect_ratio: f64,
@@ -40,53 +60,155 @@ pub struct Camera {
     defocus_disk_u: Vec3,   //  散焦圆盘的水平半径
     defocus_disk_v: Vec3,   //  Defocus disk vertical radius
     pub background: Color,  // 场景背景
+    pub sky: Option<Arc<Sky>>, // 设置后代替纯色背景
+    pub integrator: Integrator, // 光传输算法
+    pub emitters: Option<Arc<dyn Hittable + Send + Sync>>, // 只含真正发光物体的光源列表，`lights` 中有只用来引导采样的物体（如玻璃球）时设置
+    pub spectral: bool, // 路径追踪时按 hero 波长做光谱渲染，而不是直接用 RGB；仅支持 PathTracing 与 Metropolis
+    pub rgba_output: bool, // 输出带 alpha 通道的 PAM 图像用于合成，见 `render`
     sqrt_spp : i32,
     recip_sqrt_spp : f64,
 }
 
 impl Camera {
-    pub fn ray_color(r: &Ray, world: &dyn Hittable, depth: usize, background: &Color, lights : Arc<dyn Hittable + Send + Sync>) -> Color {
+    /// 一条相机射线经路径追踪得到的颜色；光谱模式下先为它采样 hero 波长，追踪后再换算回 RGB
+    pub fn path_color(&self, r: &Ray, world: &dyn Hittable, lights: Arc<dyn Hittable + Send + Sync>) -> Color {
+        let hit = world.hit(r, &Interval::new(0.001, f64::INFINITY));
+        self.path_color_from_hit(r, hit.as_ref(), world, lights)
+    }
+
+    /// 与 `path_color` 相同，`hit` 是相机射线在 `world` 中已经求出的第一个交点
+    pub fn path_color_from_hit(
+        &self,
+        r: &Ray,
+        hit: Option<&HitRecord>,
+        world: &dyn Hittable,
+        lights: Arc<dyn Hittable + Send + Sync>,
+    ) -> Color {
+        if !self.spectral {
+            return self.ray_color_from_hit(r, hit, world, self.max_depth, lights);
+        }
+        let lambdas = spectrum::sample_hero_wavelengths();
+        let mut r = *r;
+        r.wavelengths = Some(lambdas);
+        let l = self.ray_color_from_hit(&r, hit, world, self.max_depth, lights);
+        spectrum::spectrum_to_rgb(&l, &lambdas)
+    }
+
+    pub fn ray_color(&self, r: &Ray, world: &dyn Hittable, depth: usize, lights : Arc<dyn Hittable + Send + Sync>) -> Color {
         if depth <= 0 {
             return Color::new(0.0, 0.0, 0.0);
         }
-        let ray_t = Interval::new(0.001, f64::INFINITY);
-        if let Some(rec) = world.hit(r, &ray_t) {
-            let color_from_emission = rec.mat.emitted(r, &rec,rec.u, rec.v, &rec.p);
+        let hit = world.hit(r, &Interval::new(0.001, f64::INFINITY));
+        self.ray_color_from_hit(r, hit.as_ref(), world, depth, lights)
+    }
 
-            if let Some(srec) = rec.mat.scatter(r, &rec) {
-                if let Some(pdf) = srec.pdf_ptr.as_ref() {
-                    let light_ptr = Arc::new(HittablePdf::new(lights.clone(), rec.p));
-                    let p = Arc::new(MixturePdf::new(light_ptr, pdf.clone()));
+    //  `hit` 是射线 `r` 在 `world` 中的第一个交点
+    fn ray_color_from_hit(
+        &self,
+        r: &Ray,
+        hit: Option<&HitRecord>,
+        world: &dyn Hittable,
+        depth: usize,
+        lights: Arc<dyn Hittable + Send + Sync>,
+    ) -> Color {
+        if depth == 0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        match hit {
+            //  射线在吸收性电介质中走过的这一段按 Beer–Lambert 定律衰减
+            Some(rec) => r.medium_transmittance(rec.t) * self.shade(r, rec, world, depth, lights),
+            None => r.medium_transmittance(f64::INFINITY) * r.spectrum(&self.background_color(r)),
+        }
+    }
 
-                    let scattered = Ray::new(rec.p, p.generate(), r.time());
-                    let pdf_value = p.value(&scattered.direction());
+    //  射线 `r` 命中 `rec` 后带回的辐亮度：自发光加上按混合采样继续追踪的散射光
+    fn shade(&self, r: &Ray, rec: &HitRecord, world: &dyn Hittable, depth: usize, lights : Arc<dyn Hittable + Send + Sync>) -> Color {
+        let color_from_emission = r.spectrum(&rec.mat.emitted(r, rec,rec.u, rec.v, &rec.p));
 
-                    let scattering_pdf = rec.mat.scattering_pdf(r, &rec, &scattered);
-                    let sample_color = Camera::ray_color(&scattered, world, depth - 1, background, lights);
-                    let color_from_scatter = 
-                        (scattering_pdf * srec.attenuation * sample_color) / pdf_value;
+        if let Some(srec) = rec.mat.scatter(r, rec) {
+            if let Some(pdf) = srec.pdf_ptr.as_ref() {
+                let light_ptr = Arc::new(HittablePdf::new(lights.clone(), rec.p));
+                let p = Arc::new(MixturePdf::new(light_ptr, pdf.clone()));
 
-                    return color_from_emission + color_from_scatter;
-                }else {
-                    if let Some(pdf_ray)  = srec.skip_pdf_ray {
-                        return srec.attenuation * Camera::ray_color(&pdf_ray, world, depth - 1, background, lights);
-                    }else {
-                        return color_from_emission;
-                    }
+                let scattered = r.scattered(rec.p, p.generate());
+                let pdf_value = p.value(&scattered.direction());
+                //  粗糙电介质等采样到无效方向（例如反射到表面以下）时密度为 0，这个样本没有贡献
+                if pdf_value <= 0.0 {
+                    return color_from_emission;
                 }
 
-                // let p = attenuation.max_component().min(0.95);
-                // if rtweekend::random_double() < p {
-                //     let scattering_pdf = rec.mat.scattering_pdf(r, &rec, &scattered);
-                //     pdf_value = scattering_pdf;
-                //     let color_from_scatter =
-                //         scattering_pdf * attenuation * Camera::ray_color(&scattered, world, depth - 1, background) / pdf_value;
-                //     return color_from_emission + color_from_scatter / p;
-                // }
+                let scattering_value = r.spectrum(&rec.mat.scattering_value(r, rec, &scattered));
+                let sample_color = self.ray_color(&scattered, world, depth - 1, lights);
+                let color_from_scatter = (scattering_value * sample_color) / pdf_value;
+
+                return color_from_emission + color_from_scatter;
+            }else {
+                if let Some(pdf_ray)  = srec.skip_pdf_ray {
+                    let mut attenuation = r.spectrum(&srec.attenuation);
+                    //  色散使路径只保留第一个 hero 波长，其余波长在此终止，贡献转移给它：
+                    //  `spectrum_to_rgb` 是各波长单独估计的平均，而第一个波长本身也在可见光范围内均匀分布，
+                    //  单独用它乘以 HERO_WAVELENGTHS 仍是无偏估计，只是方差更大
+                    if r.wavelengths.is_some() && r.wavelength.is_none() && pdf_ray.wavelength.is_some() {
+                        attenuation = attenuation * Color::new(HERO_WAVELENGTHS as f64, 0.0, 0.0);
+                    }
+                    return attenuation * self.ray_color(&pdf_ray, world, depth - 1, lights);
+                }else {
+                    return color_from_emission;
+                }
             }
-            return color_from_emission;
-        } else {
-            *background
+
+            // let p = attenuation.max_component().min(0.95);
+            // if rtweekend::random_double() < p {
+            //     let scattering_pdf = rec.mat.scattering_pdf(r, rec, &scattered);
+            //     pdf_value = scattering_pdf;
+            //     let color_from_scatter =
+            //         scattering_pdf * attenuation * Camera::ray_color(&scattered, world, depth - 1, background) / pdf_value;
+            //     return color_from_emission + color_from_scatter / p;
+            // }
+        }
+        color_from_emission
+    }
+
+    //  射线没有击中任何物体时返回的颜色
+    pub fn background_color(&self, r: &Ray) -> Color {
+        match &self.sky {
+            Some(sky) => sky.value(r.direction()),
+            None => self.background,
+        }
+    }
+
+    //  按当前积分器估计一条相机射线带回的辐亮度（按图块渲染的积分器），`hit` 是它在 `world` 中的第一个交点。
+    //  `lights` 用于方向采样，双向路径追踪的光源子路径从 `emitters` 出发
+    fn li<'a>(
+        &self,
+        r: &Ray,
+        hit: Option<&HitRecord<'a>>,
+        world: &'a dyn Hittable,
+        (lights, emitters): (&'a Arc<dyn Hittable + Send + Sync>, &'a Arc<dyn Hittable + Send + Sync>),
+        photon_maps: Option<&PhotonMaps>,
+    ) -> Color {
+        match self.integrator {
+            Integrator::PathTracing => self.path_color_from_hit(r, hit, world, lights.clone()),
+            Integrator::Bidirectional => bdpt::li(self, r, hit.cloned(), world, emitters.as_ref()),
+            Integrator::PhotonMapping { .. } => match photon_maps {
+                Some(maps) => maps.li_from_hit(self, r, hit, world, lights, self.max_depth),
+                None => Color::new(0.0, 0.0, 0.0),
+            },
+            //  这两种方式不经过图块渲染，已在 `render` 开头处理
+            Integrator::LightTracing | Integrator::Metropolis(_) => Color::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    //  相机射线的第一个交点在 RGBA 输出中的覆盖情况
+    fn coverage(&self, hit: Option<&HitRecord>) -> Coverage {
+        if !self.rgba_output {
+            return Coverage::Opaque;
+        }
+        match hit {
+            None => Coverage::Transparent,
+            Some(rec) if rec.mat.is_holdout() => Coverage::Transparent,
+            Some(rec) if rec.mat.is_shadow_catcher() => Coverage::ShadowCatcher,
+            Some(_) => Coverage::Opaque,
         }
     }
 
@@ -114,6 +236,11 @@ impl Camera {
             defocus_disk_u: Vec3::new(0.0, 0.0, 0.0),
             defocus_disk_v: Vec3::new(0.0, 0.0, 0.0),
             background: Color::new(0.0, 0.0, 0.0),
+            sky: None,
+            integrator: Integrator::PathTracing,
+            emitters: None,
+            spectral: false,
+            rgba_output: false,
             sqrt_spp : 0,
             recip_sqrt_spp : 0.0,
         }
@@ -157,18 +284,45 @@ impl Camera {
         self.defocus_disk_v = self.v * defocus_radius;
     }
 
+    /// 渲染整张图并写入 `writer`。`rgba_output` 为 false 时输出 PPM；为 true 时输出带 alpha 通道、颜色预乘 alpha 的 PAM，
+    /// 合成时结果为 颜色 + (1 - alpha)·背景。每个像素的不透明度是各样本覆盖情况的平均：
+    /// - 未击中物体或直接看到 `Holdout` 的样本完全透明，不计入颜色
+    /// - 直接看到 `ShadowCatcher` 的样本用当前积分器分别在完整场景与只有发光物体的场景中估计它反射的光，
+    ///   整个像素上的这类样本合起来，不透明度为 1 - 实际光照亮度 / 没有遮挡时的光照亮度（只表示变暗），
+    ///   实际光照中超出 (1 - 不透明度)·没有遮挡时光照的部分（其他物体反射过来的光）作为颜色叠加
+    /// - 光源追踪与 Metropolis 整张图一起累加，alpha 恒为 1
     pub fn render<W: Write + Send>(
         &self,
         world: Arc<dyn Hittable>,
         mut writer: W,
         lights : Arc<dyn Hittable + Send + Sync>
     ) -> std::io::Result<()> {
-        writeln!(writer, "P3")?;
-        writeln!(writer, "{} {}", self.image_width, self.image_height)?;
-        writeln!(writer, "255")?;
+        //  hero 波长由 `path_color` 采样，只有基于它的积分器支持光谱模式
+        if self.spectral && !matches!(self.integrator, Integrator::PathTracing | Integrator::Metropolis(_)) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("spectral rendering is not supported by {:?}", self.integrator),
+            ));
+        }
+
+        //  从光源出发的路径只能从真正发光的物体开始，阴影接收面没有遮挡时的光照也只包含它们
+        let emitters = self.emitters.clone().unwrap_or_else(|| lights.clone());
+
+        //  光源追踪与 Metropolis 的贡献会落在任意像素上，直接累加到整张图，不按图块划分
+        let full_frame = match self.integrator {
+            Integrator::LightTracing => Some(light_tracer::render(self, world.as_ref(), emitters.as_ref())),
+            Integrator::Metropolis(options) => Some(mlt::render(self, world.as_ref(), &lights, &options)),
+            _ => None,
+        };
+        if let Some(framebuffer) = full_frame {
+            let alpha = vec![1.0; framebuffer.len()];
+            self.write_image(&mut writer, &framebuffer, &alpha)?;
+            eprintln!("Done.                 \n");
+            return Ok(());
+        }
 
         let framebuffer = Arc::new(Mutex::new(vec![
-            Color::new(0.0, 0.0, 0.0);
+            (Color::new(0.0, 0.0, 0.0), 1.0);
             self.image_width * self.image_height
         ]));
 
@@ -179,6 +333,31 @@ impl Camera {
 
         let camera_ptr = Arc::new(self.clone());
 
+        //  光子映射需要在渲染前先建立光子图
+        let photon_maps = match self.integrator {
+            Integrator::PhotonMapping { photons, radius } => Some(PhotonMaps::build(
+                world.as_ref(),
+                emitters.as_ref(),
+                photons,
+                radius,
+                max_depth,
+            )),
+            _ => None,
+        };
+        let photon_maps = photon_maps.as_ref();
+        //  阴影接收面没有遮挡时的光照在只有发光物体的场景中估计，光子映射需要为它另建一份光子图
+        let backdrop_maps = match self.integrator {
+            Integrator::PhotonMapping { photons, radius } if self.rgba_output => Some(PhotonMaps::build(
+                emitters.as_ref(),
+                emitters.as_ref(),
+                photons,
+                radius,
+                max_depth,
+            )),
+            _ => None,
+        };
+        let backdrop_maps = backdrop_maps.as_ref();
+
         let chunk_width = (self.image_width + WIDTH_PARTITION - 1) / WIDTH_PARTITION;
         let chunk_height = (self.image_height + HEIGHT_PARTITION - 1) / HEIGHT_PARTITION;
 
@@ -202,6 +381,7 @@ impl Camera {
                     let cam = Arc::clone(&camera_ptr);
                     let world = Arc::clone(&world);
                     let lights = Arc::clone(&lights);
+                    let emitters = Arc::clone(&emitters);
 
                     let thread_count = Arc::clone(&thread_count);
                     let thread_control_cvar = Arc::clone(&thread_control_cvar);
@@ -214,33 +394,51 @@ impl Camera {
 
                     s.spawn(move |_| {
                         let mut local_buffer =
-                            vec![Color::new(0.0, 0.0, 0.0); (y_max - y_min) * (x_max - x_min)];
+                            vec![(Color::new(0.0, 0.0, 0.0), 1.0); (y_max - y_min) * (x_max - x_min)];
                         for j in y_min..y_max {
                             for i in x_min..x_max {
                                 let mut pixel_color = Color::new(0.0, 0.0, 0.0);
+                                let mut opaque = 0.0;
+                                let mut catcher = 0.0;
+                                let (mut lit, mut unshadowed) = (Color::new(0.0, 0.0, 0.0), Color::new(0.0, 0.0, 0.0));
                                 for s_j in 0..cam.sqrt_spp {
                                     for s_i in 0..cam.sqrt_spp {
                                         let r = cam.get_ray(i, j, s_i as usize, s_j as usize);
-                                        pixel_color += Camera::ray_color(
-                                            &r,
-                                            world.as_ref(),
-                                            max_depth,
-                                            &self.background,
-                                            lights.clone(),
-                                        );
+                                        let hit = world.hit(&r, &Interval::new(0.001, f64::INFINITY));
+                                        match cam.coverage(hit.as_ref()) {
+                                            Coverage::Opaque => {
+                                                pixel_color +=
+                                                    cam.li(&r, hit.as_ref(), world.as_ref(), (&lights, &emitters), photon_maps);
+                                                opaque += 1.0;
+                                            }
+                                            Coverage::Transparent => {}
+                                            //  同一个交点分别在完整场景与只有光源的场景中继续追踪
+                                            Coverage::ShadowCatcher => {
+                                                catcher += 1.0;
+                                                lit += cam.li(&r, hit.as_ref(), world.as_ref(), (&lights, &emitters), photon_maps);
+                                                unshadowed += cam.li(
+                                                    &r,
+                                                    hit.as_ref(),
+                                                    emitters.as_ref(),
+                                                    (&emitters, &emitters),
+                                                    backdrop_maps,
+                                                );
+                                            }
+                                        }
                                     }
                                 }
-                                // for _sample in 0..cam.sample_per_pixel {
-                                //     let r = cam.get_ray(i, j);
-                                //     pixel_color += Camera::ray_color(
-                                //         &r,
-                                //         world.as_ref(),
-                                //         max_depth,
-                                //         &self.background,
-                                //     );
-                                // }
+                                //  阴影按亮度之比计算；其余部分（其他物体反射到接收面上的光）作为预乘颜色输出
+                                let shadow = if luminance(&unshadowed) > 0.0 {
+                                    1.0 - (luminance(&lit) / luminance(&unshadowed)).clamp(0.0, 1.0)
+                                } else {
+                                    0.0
+                                };
+                                let residual = lit - (1.0 - shadow) * unshadowed;
+                                let indirect = Color::new(residual.x().max(0.0), residual.y().max(0.0), residual.z().max(0.0));
+                                let alpha = cam.pixel_samples_scale * (opaque + catcher * shadow);
+                                pixel_color += indirect;
                                 let idx = (j - y_min) * (x_max - x_min) + (i - x_min);
-                                local_buffer[idx] = cam.pixel_samples_scale * pixel_color;
+                                local_buffer[idx] = (cam.pixel_samples_scale * pixel_color, alpha);
                             }
                         }
                         let mut fb_locked = fb.lock().unwrap();
@@ -258,17 +456,79 @@ impl Camera {
         })
         .unwrap();
 
-        for j in 0..height {
-            for i in 0..width {
-                let fb = framebuffer.lock().unwrap();
-                Color::write_color(&mut writer, &fb[j * width + i])?;
+        let fb = framebuffer.lock().unwrap();
+        let (colors, alpha): (Vec<Color>, Vec<f64>) = fb.iter().copied().unzip();
+        self.write_image(&mut writer, &colors, &alpha)?;
+
+        eprintln!("Done.                 \n");
+        Ok(())
+    }
+
+    //  以 PPM（P3）格式输出整张图；RGBA 输出时改用 PAM（P7），颜色保持预乘 alpha（线性空间中预乘后再做伽马校正），
+    //  这样 alpha 为 0 而颜色不为 0 的加性部分（阴影接收面上的反射光）也能保留
+    fn write_image<W: Write>(&self, writer: &mut W, framebuffer: &[Color], alpha: &[f64]) -> std::io::Result<()> {
+        if self.rgba_output {
+            writeln!(writer, "P7")?;
+            writeln!(writer, "WIDTH {}", self.image_width)?;
+            writeln!(writer, "HEIGHT {}", self.image_height)?;
+            writeln!(writer, "DEPTH 4")?;
+            writeln!(writer, "MAXVAL 255")?;
+            writeln!(writer, "TUPLTYPE RGB_ALPHA")?;
+            writeln!(writer, "ENDHDR")?;
+            for (pixel_color, a) in framebuffer.iter().zip(alpha) {
+                let a = a.clamp(0.0, 1.0);
+                let [r, g, b] = Color::to_rgb8(pixel_color);
+                writer.write_all(&[r, g, b, (255.999 * a) as u8])?;
             }
+            return Ok(());
         }
 
-        eprintln!("Done.                 \n");
+        writeln!(writer, "P3")?;
+        writeln!(writer, "{} {}", self.image_width, self.image_height)?;
+        writeln!(writer, "255")?;
+        for pixel_color in framebuffer {
+            Color::write_color(writer, pixel_color)?;
+        }
         Ok(())
     }
 
+    pub fn image_height(&self) -> usize {
+        self.image_height
+    }
+
+    /// 把场景中的点 `p` 连接到镜头上随机采样的一点，返回它落在的像素下标、镜头上的点与相机的重要性 We。
+    /// 点在视野之外时返回 None
+    pub fn connect_to_lens(&self, p: &Point3) -> Option<(usize, Point3, f64)> {
+        let lens = if self.defocus_angle <= 0.0 {
+            self.center
+        } else {
+            self.disk_sample()
+        };
+        let d = *p - lens;
+        let distance = d.length();
+        let cos_theta = -Vec3::dot(&d, &self.w) / distance;
+        if cos_theta <= 0.0 {
+            return None;
+        }
+
+        //  与对焦平面的交点换算成像素坐标
+        let focus_point = lens + d * (self.focus_dist / (cos_theta * distance));
+        let corner = self.pixel00_loc - 0.5 * (self.pixel_delta_u + self.pixel_delta_v);
+        let x = Vec3::dot(&(focus_point - corner), &self.pixel_delta_u)
+            / self.pixel_delta_u.length_squared();
+        let y = Vec3::dot(&(focus_point - corner), &self.pixel_delta_v)
+            / self.pixel_delta_v.length_squared();
+        if x < 0.0 || y < 0.0 || x >= self.image_width as f64 || y >= self.image_height as f64 {
+            return None;
+        }
+
+        //  像素在对焦平面上均匀采样，换算到立体角：We = focus_dist² / (A_pixel·cos³θ)
+        let pixel_area = self.pixel_delta_u.length() * self.pixel_delta_v.length();
+        let importance =
+            self.focus_dist * self.focus_dist / (pixel_area * cos_theta * cos_theta * cos_theta);
+        Some((y as usize * self.image_width + x as usize, lens, importance))
+    }
+
     pub fn get_ray(&self, i: usize, j: usize, s_i : usize, s_j : usize) -> Ray {
         // 构造一条相机射线，起点位于散焦圆盘上，方向指向像素位置 i，j 附近随机采样的点。
         let offset: Vec3 = Camera::sample_square_stratified(&self, s_i, s_j);
@@ -288,6 +548,21 @@ impl Camera {
         Ray::new(ray_origin, ray_direction, ray_time)
     }
 
+    /// 穿过图像上连续坐标 (x, y) 的相机射线，x ∈ [0, image_width)，y ∈ [0, image_height)
+    pub fn get_ray_raster(&self, x: f64, y: f64) -> Ray {
+        let pixel_sample =
+            self.pixel00_loc + ((x - 0.5) * self.pixel_delta_u) + ((y - 0.5) * self.pixel_delta_v);
+
+        let ray_origin = if self.defocus_angle <= 0.0 {
+            self.center
+        } else {
+            Camera::disk_sample(self)
+        };
+        let ray_time = rtweekend::random_double();
+
+        Ray::new(ray_origin, pixel_sample - ray_origin, ray_time)
+    }
+
     fn sample_square_stratified(&self, s_i : usize, s_j : usize) -> Vec3 {
         let px = ((s_i as f64 + random_double()) * self.recip_sqrt_spp) - 0.5;
         let py = ((s_j as f64 + random_double()) * self.recip_sqrt_spp) - 0.5;
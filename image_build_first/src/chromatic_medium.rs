@@ -0,0 +1,165 @@
+//! # `chromatic_medium.rs` 模块说明
+//!
+//! 吸收与散射分开、且逐通道不同的均匀参与介质，例如有色液体、牛奶、稀释的墨水。
+//! 介质由吸收系数 σ_a 与散射系数 σ_s 描述，消光系数 σ_t = σ_a + σ_s。
+//!
+//! 采样使用 null-collision 形式的光谱追踪：以各通道中最大的消光系数 σ̄ 作为主密度采样试探碰撞，
+//! 每次碰撞随机判定为吸收、散射或空碰撞，三者的概率取各通道系数的平均值与 σ̄ 之比：
+//! - 吸收：路径终止
+//! - 散射：按相函数散射，各通道的权重为 σ_s / avg(σ_s)
+//! - 空碰撞：沿原方向继续前进，各通道的权重为 (σ̄ - σ_t) / avg(σ̄ - σ_t)
+//!
+//! 没有发生碰撞而穿出介质的射线权重为 1，因此与 `ConstantMedium` 一样可以直接用 `hit` 表达。
+//! 空碰撞会占用一次弹射次数，各通道差异很大的浓稠介质需要相应地提高 `max_depth`。
+//! 两点之间的透射率逐通道精确计算为 exp(-σ_t·d)。
+
+use std::sync::Arc;
+
+use crate::constant_medium::boundary_segments;
+use crate::hittable::{HitRecord, Hittable, ShadingCache};
+use crate::interval::Interval;
+use crate::material::{Anisotropic, EmptyMaterial, Material, ScatterRecord};
+use crate::phase::{HenyeyGreenstein, PhaseFunction};
+use crate::ray::Ray;
+use crate::rtweekend::random_double;
+use crate::vec3::{Color, Vec3};
+
+//  空碰撞：不改变方向，只按通道调整权重
+#[derive(Debug)]
+struct NullCollision {
+    weight: Color,
+}
+
+impl Material for NullCollision {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        Some(ScatterRecord {
+            attenuation: self.weight,
+            pdf_ptr: None,
+            skip_pdf_ray: Some(r_in.scattered(rec.p, *r_in.direction())),
+            null_collision: true,
+        })
+    }
+}
+
+pub struct ChromaticMedium {
+    boundary: Arc<dyn Hittable>,
+    sigma_t: Color,
+    sigma_bar: f64,
+    absorb_probability: f64,
+    scatter_probability: f64,
+    absorb: EmptyMaterial,
+    phase_function: Anisotropic,
+    null_collision: NullCollision,
+}
+
+fn average(c: &Color) -> f64 {
+    (c.x() + c.y() + c.z()) / 3.0
+}
+
+impl ChromaticMedium {
+    /// 各向同性散射的有色介质，`sigma_a` 与 `sigma_s` 为每单位长度的吸收与散射系数
+    pub fn new(boundary: Arc<dyn Hittable>, sigma_a: Color, sigma_s: Color) -> Self {
+        Self::new_with_phase(boundary, sigma_a, sigma_s, Arc::new(HenyeyGreenstein::new(0.0)))
+    }
+
+    pub fn new_with_phase(
+        boundary: Arc<dyn Hittable>,
+        sigma_a: Color,
+        sigma_s: Color,
+        phase: Arc<dyn PhaseFunction>,
+    ) -> Self {
+        let sigma_t = sigma_a + sigma_s;
+        let sigma_bar = sigma_t.max_component();
+        let sigma_n = Color::new(sigma_bar, sigma_bar, sigma_bar) - sigma_t;
+
+        let (absorb_probability, scatter_probability) = if sigma_bar > 0.0 {
+            (average(&sigma_a) / sigma_bar, average(&sigma_s) / sigma_bar)
+        } else {
+            (0.0, 0.0)
+        };
+        let scatter_weight = if average(&sigma_s) > 0.0 {
+            sigma_s / average(&sigma_s)
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        };
+        let null_weight = if average(&sigma_n) > 0.0 {
+            sigma_n / average(&sigma_n)
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        };
+
+        Self {
+            boundary,
+            sigma_t,
+            sigma_bar,
+            absorb_probability,
+            scatter_probability,
+            absorb: EmptyMaterial,
+            phase_function: Anisotropic::new_with_color(scatter_weight, phase),
+            null_collision: NullCollision {
+                weight: null_weight,
+            },
+        }
+    }
+}
+
+impl Hittable for ChromaticMedium {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: &Interval) -> Option<HitRecord<'a>> {
+        if self.sigma_bar <= 0.0 {
+            return None;
+        }
+        let ray_length = r.direction().length();
+        let mut hit_distance = -random_double().ln() / self.sigma_bar;
+
+        for segment in boundary_segments(&*self.boundary, r, ray_t) {
+            let distance_inside_boundary = segment.size() * ray_length;
+            if hit_distance > distance_inside_boundary {
+                hit_distance -= distance_inside_boundary;
+                continue;
+            }
+
+            let t = segment.min + hit_distance / ray_length;
+            let xi = random_double();
+            let mat: &dyn Material = if xi < self.absorb_probability {
+                &self.absorb
+            } else if xi < self.absorb_probability + self.scatter_probability {
+                &self.phase_function
+            } else {
+                &self.null_collision
+            };
+
+            return Some(HitRecord {
+                t,
+                p: r.at(t),
+                normal: Vec3::new(1.0, 0.0, 0.0), // arbitrary
+                front_face: true,                 // arbitrary
+                mat,
+                u: 0.0,
+                v: 0.0,
+                dpdu: Vec3::new(0.0, 0.0, 0.0), // 没有表面，也就没有切线
+                dpdv: Vec3::new(0.0, 0.0, 0.0),
+                shading: ShadingCache::default(),
+                object: self as *const Self as usize,
+            });
+        }
+
+        None
+    }
+
+    fn bounding_box(&self) -> crate::AABB::Aabb {
+        self.boundary.bounding_box()
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: &Interval) -> Color {
+        let ray_length = r.direction().length();
+        let distance: f64 = boundary_segments(&*self.boundary, r, ray_t)
+            .iter()
+            .map(|segment| segment.size() * ray_length)
+            .sum();
+        Color::new(
+            (-self.sigma_t.x() * distance).exp(),
+            (-self.sigma_t.y() * distance).exp(),
+            (-self.sigma_t.z() * distance).exp(),
+        )
+    }
+}
@@ -0,0 +1,200 @@
+//! # `coated.rs` 模块说明
+//!
+//! 在任意基底材质上覆盖一层透明电介质涂层：塑料、车漆、上过清漆的木头。
+//!
+//! - 涂层本身只反射，菲涅尔反射率由涂层折射率决定，粗糙度为 0 时是理想镜面，否则为 GGX 微表面
+//! - 进入涂层与离开涂层各乘一次透射率 1 - F，之后由基底材质照常散射；
+//!   忽略涂层内的折射与层间的多次反射，因此基底看到的方向与涂层外相同
+//! - 采样时以出射方向上的菲涅尔反射率 F(cosθo) 为概率选择涂层，否则交给基底：
+//!   两者都不是镜面时混合为一个概率密度；有一方是镜面时随机选择。
+//!   BSDF 与概率密度按 `pdf_probability`（确定的选择概率）归一化，不依赖某一次随机选择的结果
+
+use std::sync::Arc;
+
+use crate::hittable::HitRecord;
+use crate::material::{Material, ScatterRecord};
+use crate::microfacet::{fresnel_dielectric, MicrofacetPdf, TrowbridgeReitz};
+use crate::onb::Onb;
+use crate::pdf::{Pdf, WeightedMixturePdf};
+use crate::ray::Ray;
+use crate::rtweekend::random_double;
+use crate::vec3::{Color, Point3, Vec3};
+
+#[derive(Debug)]
+pub struct Coated {
+    base: Arc<dyn Material>,
+    refraction_index: f64,
+    distribution: TrowbridgeReitz,
+}
+
+impl Coated {
+    /// 折射率为 `refraction_index`、粗糙度为 `roughness` 的涂层覆盖在 `base` 上
+    pub fn new(base: Arc<dyn Material>, refraction_index: f64, roughness: f64) -> Self {
+        Self {
+            base,
+            refraction_index,
+            distribution: TrowbridgeReitz::from_roughness(roughness, roughness),
+        }
+    }
+
+    //  与宏观法线夹角余弦为 `cos_theta` 的方向上涂层的反射率
+    fn fresnel(&self, cos_theta: f64) -> f64 {
+        fresnel_dielectric(cos_theta, self.refraction_index)
+    }
+
+    fn cos_out(r_in: &Ray, rec: &HitRecord) -> f64 {
+        -Vec3::dot(&Vec3::unit_vector(r_in.direction()), &rec.normal)
+    }
+
+    fn cos_in(rec: &HitRecord, scattered: &Ray) -> f64 {
+        Vec3::dot(&Vec3::unit_vector(scattered.direction()), &rec.normal)
+    }
+
+    //  粗糙涂层的 f·cosθi
+    fn coat_value(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let uvw = Onb::new(rec.normal);
+        let wo = Vec3::unit_vector(&uvw.to_local(&-*r_in.direction()));
+        let wi = Vec3::unit_vector(&uvw.to_local(scattered.direction()));
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return 0.0;
+        }
+        let m = Vec3::unit_vector(&(wo + wi));
+        let d = self.distribution.d(&m);
+        let g = self.distribution.g(&wo, &wi);
+        d * g * self.fresnel(Vec3::dot(&wo, &m)) / (4.0 * wo.z())
+    }
+
+    fn coat_pdf(&self, r_in: &Ray, rec: &HitRecord) -> MicrofacetPdf {
+        MicrofacetPdf::new(rec.normal, -*r_in.direction(), self.distribution)
+    }
+}
+
+impl Material for Coated {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let cos_out = Coated::cos_out(r_in, rec);
+        if cos_out <= 0.0 {
+            return self.base.scatter(r_in, rec);
+        }
+        let f_out = self.fresnel(cos_out);
+        let base = self.base.scatter(r_in, rec);
+        let base_pdf = base.as_ref().and_then(|srec| srec.pdf_ptr.clone());
+
+        //  两者都可以求概率密度：按菲涅尔反射率混合
+        if let (false, Some(base_pdf)) = (self.distribution.is_smooth(), base_pdf.clone()) {
+            let coat_pdf: Arc<dyn Pdf + Send + Sync> = Arc::new(self.coat_pdf(r_in, rec));
+            return Some(ScatterRecord {
+                attenuation: base.map_or(Color::new(0.0, 0.0, 0.0), |srec| srec.attenuation),
+                pdf_ptr: Some(Arc::new(WeightedMixturePdf::new(vec![
+                    (f_out, coat_pdf),
+                    (1.0 - f_out, base_pdf),
+                ]))),
+                skip_pdf_ray: None,
+                null_collision: false,
+            });
+        }
+
+        if random_double() < f_out {
+            if self.distribution.is_smooth() {
+                let reflected = Vec3::reflect(r_in.direction(), &rec.normal);
+                return Some(ScatterRecord {
+                    attenuation: Color::new(1.0, 1.0, 1.0),
+                    pdf_ptr: None,
+                    skip_pdf_ray: Some(r_in.scattered(rec.p, reflected)),
+                    null_collision: false,
+                });
+            }
+            return Some(ScatterRecord {
+                attenuation: Color::new(1.0, 1.0, 1.0),
+                pdf_ptr: Some(Arc::new(self.coat_pdf(r_in, rec))),
+                skip_pdf_ray: None,
+                null_collision: false,
+            });
+        }
+
+        //  穿过涂层交给基底；进入时的 1 - F 与选择概率抵消
+        let mut srec = base?;
+        if let Some(scattered) = srec.skip_pdf_ray.as_ref() {
+            let cos_in = Coated::cos_in(rec, scattered);
+            if cos_in > 0.0 {
+                srec.attenuation *= 1.0 - self.fresnel(cos_in);
+            }
+        }
+        Some(srec)
+    }
+
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord, u: f64, v: f64, p: &Point3) -> Color {
+        self.base.emitted(r_in, rec, u, v, p)
+    }
+
+    fn is_two_sided_emitter(&self) -> bool {
+        self.base.is_two_sided_emitter()
+    }
+
+    //  涂层本身用几何法线，伴随修正沿用基底的着色法线
+    fn shading_normal(&self, rec: &HitRecord) -> Vec3 {
+        self.base.shading_normal(rec)
+    }
+
+    fn is_holdout(&self) -> bool {
+        self.base.is_holdout()
+    }
+
+    fn is_shadow_catcher(&self) -> bool {
+        self.base.is_shadow_catcher()
+    }
+
+    fn pdf_probability(&self, r_in: &Ray, rec: &HitRecord) -> f64 {
+        let base = self.base.pdf_probability(r_in, rec);
+        let cos_out = Coated::cos_out(r_in, rec);
+        if cos_out <= 0.0 {
+            return base;
+        }
+        let f_out = self.fresnel(cos_out);
+        if self.distribution.is_smooth() {
+            //  选中基底且基底给出概率密度
+            (1.0 - f_out) * base
+        } else {
+            //  基底给出概率密度时与涂层混合，否则以 F 的概率选中涂层
+            base + (1.0 - base) * f_out
+        }
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let cos_out = Coated::cos_out(r_in, rec);
+        if cos_out <= 0.0 || self.distribution.is_smooth() {
+            return self.base.scattering_pdf(r_in, rec, scattered);
+        }
+        let f_out = self.fresnel(cos_out);
+        let base = self.base.pdf_probability(r_in, rec);
+        let coat_pdf = self.coat_pdf(r_in, rec).value(scattered.direction());
+        let base_pdf = if base > 0.0 {
+            self.base.scattering_pdf(r_in, rec, scattered)
+        } else {
+            0.0
+        };
+        (f_out * coat_pdf + (1.0 - f_out) * base * base_pdf) / (base + (1.0 - base) * f_out)
+    }
+
+    fn scattering_value(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        let cos_out = Coated::cos_out(r_in, rec);
+        if cos_out <= 0.0 {
+            return self.base.scattering_value(r_in, rec, scattered);
+        }
+        let f_out = self.fresnel(cos_out);
+
+        //  穿过涂层的部分：选中基底的概率 1 - F 与进入涂层的透射率抵消
+        let cos_in = Coated::cos_in(rec, scattered);
+        let through = if cos_in > 0.0 {
+            self.base.scattering_value(r_in, rec, scattered) * (1.0 - self.fresnel(cos_in))
+        } else {
+            self.base.scattering_value(r_in, rec, scattered)
+        };
+        if self.distribution.is_smooth() {
+            return through;
+        }
+
+        let base = self.base.pdf_probability(r_in, rec);
+        let coat = self.coat_value(r_in, rec, scattered);
+        (Color::new(coat, coat, coat) + through * ((1.0 - f_out) * base)) / (base + (1.0 - base) * f_out)
+    }
+}
@@ -1,8 +1,9 @@
 use std::sync::Arc;
 
-use crate::hittable::{HitRecord, Hittable};
+use crate::hittable::{HitRecord, Hittable, ShadingCache};
 use crate::interval::Interval;
-use crate::material::{Isotropic, Material};
+use crate::material::{Anisotropic, Isotropic, Material};
+use crate::phase::PhaseFunction;
 use crate::ray::Ray;
 use crate::rtweekend::random_double;
 use crate::texture::Texture;
@@ -36,55 +37,161 @@ impl ConstantMedium {
             phase_function,
         }
     }
-}
 
-impl Hittable for ConstantMedium {
-    fn hit<'a>(&'a self, r: &Ray, ray_t: &Interval) -> Option<HitRecord<'a>> {
-        let rec1 = self.boundary.hit(r, &Interval::universe())?;
-        let rec2 = self
-            .boundary
-            .hit(r, &Interval::new(rec1.t + 0.0001, f64::INFINITY))?;
+    /// 用给定的相函数代替各向同性散射，例如 `HenyeyGreenstein::new(0.8)` 的强前向散射
+    pub fn new_with_phase(
+        boundary: Arc<dyn Hittable>,
+        density: f64,
+        albedo: Color,
+        phase: Arc<dyn PhaseFunction>,
+    ) -> Self {
+        let phase_function = Arc::new(Anisotropic::new_with_color(albedo, phase));
+        Self {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function,
+        }
+    }
+}
 
-        let mut t1 = rec1.t;
-        let mut t2 = rec2.t;
+/// 射线在 `ray_t` 范围内位于 `boundary` 内部的各段参数区间。
+/// 依次求出边界上的所有交点，按 `front_face` 判断进入还是离开并记录嵌套层数，
+/// 因此边界可以是非凸的，也可以是若干个相互重叠的封闭物体
+pub fn boundary_segments(boundary: &dyn Hittable, r: &Ray, ray_t: &Interval) -> Vec<Interval> {
+    let mut segments = Vec::new();
+    let mut depth = 0;
+    let mut entry = -f64::INFINITY;
+    let mut t = -f64::INFINITY;
 
-        if t1 < ray_t.min {
-            t1 = ray_t.min;
+    //  限制交点个数，防止数值问题导致死循环
+    for _ in 0..64 {
+        let Some(rec) = boundary.hit(r, &Interval::new(t, f64::INFINITY)) else {
+            break;
+        };
+        if rec.front_face {
+            if depth == 0 {
+                entry = rec.t;
+            }
+            depth += 1;
+        } else if depth <= 1 {
+            //  射线起点之前的部分不算
+            let t1 = entry.max(ray_t.min).max(0.0);
+            let t2 = rec.t.min(ray_t.max);
+            if t1 < t2 {
+                segments.push(Interval::new(t1, t2));
+            }
+            depth = 0;
+            entry = rec.t;
+        } else {
+            depth -= 1;
         }
-        if t2 > ray_t.max {
-            t2 = ray_t.max;
+        if rec.t >= ray_t.max {
+            break;
         }
+        t = rec.t + 0.0001;
+    }
 
-        if t1 >= t2 {
-            return None;
-        }
-        if t1 < 0.0 {
-            t1 = 0.0;
-        }
+    segments
+}
 
+impl Hittable for ConstantMedium {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: &Interval) -> Option<HitRecord<'a>> {
         let ray_length = r.direction().length();
-        let distance_inside_boundary = (t2 - t1) * ray_length;
-        let hit_distance = self.neg_inv_density * random_double().ln();
+        let mut hit_distance = self.neg_inv_density * random_double().ln();
+
+        //  按指数分布采样的自由程依次穿过边界内的各段
+        for segment in boundary_segments(&*self.boundary, r, ray_t) {
+            let distance_inside_boundary = segment.size() * ray_length;
+            if hit_distance > distance_inside_boundary {
+                hit_distance -= distance_inside_boundary;
+                continue;
+            }
 
-        if hit_distance > distance_inside_boundary {
-            return None;
+            let t = segment.min + hit_distance / ray_length;
+            let p = r.at(t);
+
+            return Some(HitRecord {
+                t,
+                p,
+                normal: Vec3::new(1.0, 0.0, 0.0), // arbitrary
+                front_face: true,                 // arbitrary
+                mat: &*self.phase_function,
+                u: 0.0,
+                v: 0.0,
+                dpdu: Vec3::new(0.0, 0.0, 0.0), // 没有表面，也就没有切线
+                dpdv: Vec3::new(0.0, 0.0, 0.0),
+                shading: ShadingCache::default(),
+                object: self as *const Self as usize,
+            });
         }
 
-        let t = t1 + hit_distance / ray_length;
-        let p = r.at(t);
-
-        Some(HitRecord {
-            t,
-            p,
-            normal: Vec3::new(1.0, 0.0, 0.0), // arbitrary
-            front_face: true,                 // arbitrary
-            mat: &*self.phase_function,
-            u: 0.0,
-            v: 0.0,
-        })
+        None
     }
 
     fn bounding_box(&self) -> crate::AABB::Aabb {
         self.boundary.bounding_box()
     }
+
+    fn transmittance(&self, r: &Ray, ray_t: &Interval) -> Color {
+        let ray_length = r.direction().length();
+        let distance: f64 = boundary_segments(&*self.boundary, r, ray_t)
+            .iter()
+            .map(|segment| segment.size() * ray_length)
+            .sum();
+        let t = (distance / self.neg_inv_density).exp();
+        Color::new(t, t, t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::HittableList;
+    use crate::material::EmptyMaterial;
+    use crate::sphere::Sphere;
+    use crate::vec3::{Point3, Vec3};
+
+    fn sphere(x: f64) -> Arc<Sphere> {
+        Arc::new(Sphere::new_stationary(Point3::new(x, 0.0, 0.0), 1.0, Arc::new(EmptyMaterial)))
+    }
+
+    fn assert_segments(segments: &[Interval], expected: &[(f64, f64)]) {
+        assert_eq!(segments.len(), expected.len(), "{:?}", segments);
+        for (segment, (min, max)) in segments.iter().zip(expected) {
+            assert!((segment.min - min).abs() < 1e-9 && (segment.max - max).abs() < 1e-9, "{:?}", segments);
+        }
+    }
+
+    #[test]
+    fn segment_through_sphere() {
+        let r = Ray::new(Point3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        let all = Interval::new(0.001, f64::INFINITY);
+        assert_segments(&boundary_segments(&*sphere(0.0), &r, &all), &[(4.0, 6.0)]);
+        assert_segments(&boundary_segments(&*sphere(0.0), &r, &Interval::new(0.001, 5.0)), &[(4.0, 5.0)]);
+
+        let miss = Ray::new(Point3::new(-5.0, 2.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        assert!(boundary_segments(&*sphere(0.0), &miss, &all).is_empty());
+    }
+
+    #[test]
+    fn segment_from_inside_starts_at_origin() {
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(2.0, 0.0, 0.0), 0.0);
+        let segments = boundary_segments(&*sphere(0.0), &r, &Interval::new(0.001, f64::INFINITY));
+        assert_segments(&segments, &[(0.001, 0.5)]);
+    }
+
+    #[test]
+    fn overlapping_and_disjoint_boundaries() {
+        let mut overlapping = HittableList::new();
+        overlapping.add(sphere(0.0));
+        overlapping.add(sphere(1.5));
+        let r = Ray::new(Point3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        let all = Interval::new(0.001, f64::INFINITY);
+        assert_segments(&boundary_segments(&overlapping, &r, &all), &[(4.0, 7.5)]);
+
+        let mut disjoint = HittableList::new();
+        disjoint.add(sphere(0.0));
+        disjoint.add(sphere(3.0));
+        assert_segments(&boundary_segments(&disjoint, &r, &all), &[(4.0, 6.0), (7.0, 9.0)]);
+    }
 }
@@ -0,0 +1,110 @@
+//! # `cutout.rs` 模块说明
+//!
+//! 给任意物体加上不透明度纹理，树叶、栅栏、贴花都可以用一个 `Quad` 加一张遮罩完成。
+//! 不透明度取纹理颜色在交点 (u, v, p) 处的第一个分量：
+//! - `AlphaMode::Threshold(a)`：不透明度低于 a 的地方完全透明，其余完全不透明
+//! - `AlphaMode::Stochastic`：以不透明度为概率判定是否命中，多次采样平均后呈现半透明
+//!
+//! 透明处射线直接穿过，继续寻找同一物体后面的交点。阴影射线的透射率不需要随机判定，
+//! 精确地取各交点处 (1 - 不透明度) 的乘积。作为光源采样时仍按整个表面计算。
+
+use std::sync::Arc;
+
+use crate::AABB::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::rtweekend::random_double;
+use crate::texture::Texture;
+use crate::vec3::{Color, Point3, Vec3};
+
+//  一条射线最多穿过的透明层数，防止数值问题导致死循环
+const MAX_LAYERS: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlphaMode {
+    Threshold(f64),
+    Stochastic,
+}
+
+pub struct Cutout {
+    object: Arc<dyn Hittable>,
+    opacity: Arc<dyn Texture>,
+    mode: AlphaMode,
+}
+
+impl Cutout {
+    pub fn new(object: Arc<dyn Hittable>, opacity: Arc<dyn Texture>, mode: AlphaMode) -> Self {
+        Self {
+            object,
+            opacity,
+            mode,
+        }
+    }
+
+    //  交点处的不透明度，阈值模式下只有 0 与 1
+    fn opacity(&self, rec: &HitRecord) -> f64 {
+        let alpha = self.opacity.value(rec.u, rec.v, &rec.p).x().clamp(0.0, 1.0);
+        match self.mode {
+            AlphaMode::Threshold(threshold) => {
+                if alpha < threshold {
+                    0.0
+                } else {
+                    1.0
+                }
+            }
+            AlphaMode::Stochastic => alpha,
+        }
+    }
+}
+
+impl Hittable for Cutout {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: &Interval) -> Option<HitRecord<'a>> {
+        let mut t_min = ray_t.min;
+        for _ in 0..MAX_LAYERS {
+            let rec = self.object.hit(r, &Interval::new(t_min, ray_t.max))?;
+            let alpha = self.opacity(&rec);
+            if alpha >= 1.0 || (alpha > 0.0 && random_double() < alpha) {
+                return Some(rec);
+            }
+            t_min = rec.t + 0.0001;
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.object.bounding_box()
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        self.object.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: &Point3) -> Vec3 {
+        self.object.random(origin)
+    }
+
+    fn sample_surface(&self, time: f64) -> Option<(HitRecord<'_>, f64)> {
+        self.object.sample_surface(time)
+    }
+
+    fn surface_pdf(&self, p: &Point3, time: f64) -> f64 {
+        self.object.surface_pdf(p, time)
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: &Interval) -> Color {
+        let mut transparency = 1.0;
+        let mut t_min = ray_t.min;
+        for _ in 0..MAX_LAYERS {
+            let Some(rec) = self.object.hit(r, &Interval::new(t_min, ray_t.max)) else {
+                break;
+            };
+            transparency *= 1.0 - self.opacity(&rec);
+            if transparency <= 0.0 {
+                break;
+            }
+            t_min = rec.t + 0.0001;
+        }
+        Color::new(transparency, transparency, transparency)
+    }
+}
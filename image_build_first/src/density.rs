@@ -0,0 +1,158 @@
+//! # `density.rs` 模块说明
+//!
+//! 非均匀参与介质使用的三维密度场：
+//! - `PerlinDensity`：由 Perlin 湍流生成的程序化密度，适合云、烟
+//! - `VoxelGrid`：规则体素网格，在包围盒内做三线性插值，盒外密度为 0
+//!
+//! 密度场必须给出一个上界 `max_density`，delta tracking 与 ratio tracking 以它作为主密度
+
+use crate::perlin::Perlin;
+use crate::vec3::Point3;
+
+pub trait DensityField: Send + Sync {
+    /// 点 `p` 处的消光系数（单位长度上的密度）
+    fn density(&self, p: &Point3) -> f64;
+
+    /// 整个场内密度的上界
+    fn max_density(&self) -> f64;
+}
+
+pub struct PerlinDensity {
+    noise: Perlin,
+    density: f64,
+    scale: f64,
+}
+
+impl PerlinDensity {
+    pub fn new(density: f64, scale: f64) -> Self {
+        Self {
+            noise: Perlin::new(),
+            density,
+            scale,
+        }
+    }
+}
+
+impl DensityField for PerlinDensity {
+    fn density(&self, p: &Point3) -> f64 {
+        self.density * self.noise.trub(&(self.scale * *p), 7).clamp(0.0, 1.0)
+    }
+
+    fn max_density(&self) -> f64 {
+        self.density
+    }
+}
+
+pub struct VoxelGrid {
+    nx: usize,
+    ny: usize,
+    nz: usize,
+    data: Vec<f64>, //  按 x、y、z 的顺序展开，下标为 (z * ny + y) * nx + x
+    min: Point3,
+    max: Point3,
+    max_density: f64,
+}
+
+impl VoxelGrid {
+    pub fn new(nx: usize, ny: usize, nz: usize, data: Vec<f64>, min: Point3, max: Point3) -> Self {
+        assert_eq!(data.len(), nx * ny * nz, "voxel data does not match the grid size");
+        let max_density = data.iter().cloned().fold(0.0, f64::max);
+        Self {
+            nx,
+            ny,
+            nz,
+            data,
+            min,
+            max,
+            max_density,
+        }
+    }
+
+    fn voxel(&self, x: usize, y: usize, z: usize) -> f64 {
+        self.data[(z * self.ny + y) * self.nx + x]
+    }
+}
+
+impl DensityField for VoxelGrid {
+    fn density(&self, p: &Point3) -> f64 {
+        let n = [self.nx, self.ny, self.nz];
+        let mut index = [0; 3];
+        let mut frac = [0.0; 3];
+        for i in 0..3 {
+            if p[i] < self.min[i] || p[i] > self.max[i] || n[i] == 0 {
+                return 0.0;
+            }
+
+            //  体素值位于格子中心
+            let g = (p[i] - self.min[i]) / (self.max[i] - self.min[i]) * n[i] as f64 - 0.5;
+            let g = g.clamp(0.0, (n[i] - 1) as f64);
+            index[i] = (g as usize).min(n[i].saturating_sub(2));
+            frac[i] = g - index[i] as f64;
+        }
+
+        let mut sum = 0.0;
+        for dz in 0..2 {
+            for dy in 0..2 {
+                for dx in 0..2 {
+                    let x = (index[0] + dx).min(self.nx - 1);
+                    let y = (index[1] + dy).min(self.ny - 1);
+                    let z = (index[2] + dz).min(self.nz - 1);
+                    let w = (if dx == 1 { frac[0] } else { 1.0 - frac[0] })
+                        * (if dy == 1 { frac[1] } else { 1.0 - frac[1] })
+                        * (if dz == 1 { frac[2] } else { 1.0 - frac[2] });
+                    sum += w * self.voxel(x, y, z);
+                }
+            }
+        }
+        sum
+    }
+
+    fn max_density(&self) -> f64 {
+        self.max_density
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid() -> VoxelGrid {
+        //  2×2×2 的格子，值等于 x + 2y + 4z，三线性插值应当精确重建这个线性函数
+        let data = (0..8).map(|i| ((i & 1) + 2 * ((i >> 1) & 1) + 4 * (i >> 2)) as f64).collect();
+        VoxelGrid::new(2, 2, 2, data, Point3::new(0.0, 0.0, 0.0), Point3::new(2.0, 2.0, 2.0))
+    }
+
+    #[test]
+    fn voxel_values_sit_at_cell_centers() {
+        let grid = grid();
+        assert_eq!(grid.density(&Point3::new(0.5, 0.5, 0.5)), 0.0);
+        assert_eq!(grid.density(&Point3::new(1.5, 0.5, 0.5)), 1.0);
+        assert_eq!(grid.density(&Point3::new(0.5, 1.5, 1.5)), 6.0);
+        assert_eq!(grid.density(&Point3::new(1.5, 1.5, 1.5)), 7.0);
+        assert_eq!(grid.max_density(), 7.0);
+    }
+
+    #[test]
+    fn trilinear_interpolation_between_centers() {
+        let grid = grid();
+        let p = Point3::new(0.75, 1.0, 1.25);
+        let expected = (0.75 - 0.5) + 2.0 * (1.0 - 0.5) + 4.0 * (1.25 - 0.5);
+        assert!((grid.density(&p) - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn clamps_near_faces_and_is_empty_outside() {
+        let grid = grid();
+        //  中心到边界之间取最近的体素值，边界外为 0
+        assert_eq!(grid.density(&Point3::new(0.1, 0.5, 0.5)), 0.0);
+        assert_eq!(grid.density(&Point3::new(1.9, 1.9, 1.9)), 7.0);
+        assert_eq!(grid.density(&Point3::new(-0.1, 1.0, 1.0)), 0.0);
+        assert_eq!(grid.density(&Point3::new(1.0, 2.1, 1.0)), 0.0);
+    }
+
+    #[test]
+    fn single_voxel_grid_is_constant() {
+        let grid = VoxelGrid::new(1, 1, 1, vec![0.3], Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0));
+        assert_eq!(grid.density(&Point3::new(0.9, -0.9, 0.2)), 0.3);
+    }
+}
@@ -0,0 +1,61 @@
+//! # `dispersion.rs` 模块说明
+//!
+//! 折射率随波长变化的色散模型，波长以纳米为单位，公式内部换算为微米：
+//! - `Cauchy`：n(λ) = A + B / λ²，适合大多数光学玻璃的近似
+//! - `Sellmeier`：n²(λ) = 1 + Σ Bᵢλ² / (λ² - Cᵢ)，玻璃厂商给出的标准形式
+//! - `Dispersion::from_abbe` 由 d 线折射率与阿贝数构造 Cauchy 模型
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dispersion {
+    Cauchy { a: f64, b: f64 },
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+//  夫琅禾费谱线 d、F、C 的波长（纳米）
+const LAMBDA_D: f64 = 587.6;
+const LAMBDA_F: f64 = 486.1;
+const LAMBDA_C: f64 = 656.3;
+
+impl Dispersion {
+    /// d 线折射率为 `nd`、阿贝数为 `abbe` 的玻璃，阿贝数越小色散越强
+    pub fn from_abbe(nd: f64, abbe: f64) -> Self {
+        let inv_sq = |lambda: f64| 1.0 / (lambda * 1e-3 * lambda * 1e-3);
+        let b = (nd - 1.0) / (abbe * (inv_sq(LAMBDA_F) - inv_sq(LAMBDA_C)));
+        let a = nd - b * inv_sq(LAMBDA_D);
+        Dispersion::Cauchy { a, b }
+    }
+
+    /// Schott N-BK7 冕牌玻璃
+    pub fn bk7() -> Self {
+        Dispersion::Sellmeier {
+            b: [1.039_612_12, 0.231_792_344, 1.010_469_45],
+            c: [0.006_000_698_67, 0.020_017_914_4, 103.560_653],
+        }
+    }
+
+    /// Schott SF11 重火石玻璃，色散很强
+    pub fn sf11() -> Self {
+        Dispersion::Sellmeier {
+            b: [1.737_596_95, 0.313_747_346, 1.898_781_01],
+            c: [0.013_188_707, 0.062_306_814_2, 155.236_29],
+        }
+    }
+
+    /// 波长 `lambda`（纳米）处的折射率
+    pub fn ior(&self, lambda: f64) -> f64 {
+        let l = lambda * 1e-3;
+        let l2 = l * l;
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                let n2 = 1.0 + (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum::<f64>();
+                n2.sqrt()
+            }
+        }
+    }
+
+    /// d 线（587.6 nm）处的折射率
+    pub fn ior_d(&self) -> f64 {
+        self.ior(LAMBDA_D)
+    }
+}
@@ -0,0 +1,134 @@
+//! # `heterogeneous_medium.rs` 模块说明
+//!
+//! 密度随位置变化的参与介质，密度由 `DensityField` 给出，边界可以是任意封闭（包括非凸）物体。
+//!
+//! - 散射位置用 delta tracking（Woodcock tracking）采样：以上界 σ_max 采样试探碰撞，
+//!   以 σ(p)/σ_max 的概率接受为真实碰撞，否则继续前进
+//! - 透射率用 ratio tracking 估计：在每个试探碰撞处乘以 1 - σ(p)/σ_max
+//!
+//! 两者都是无偏的，与 `ConstantMedium` 一样默认以 `Isotropic` 作为相函数，也可以通过 `new_with_phase` 指定。
+
+use std::sync::Arc;
+
+use crate::constant_medium::boundary_segments;
+use crate::density::DensityField;
+use crate::hittable::{HitRecord, Hittable, ShadingCache};
+use crate::interval::Interval;
+use crate::material::{Anisotropic, Isotropic, Material};
+use crate::phase::PhaseFunction;
+use crate::ray::Ray;
+use crate::rtweekend::random_double;
+use crate::texture::Texture;
+use crate::vec3::{Color, Vec3};
+
+pub struct HeterogeneousMedium {
+    boundary: Arc<dyn Hittable>,
+    field: Arc<dyn DensityField>,
+    phase_function: Arc<dyn Material>,
+}
+
+impl HeterogeneousMedium {
+    pub fn new_with_texture(
+        boundary: Arc<dyn Hittable>,
+        field: Arc<dyn DensityField>,
+        texture: Arc<dyn Texture>,
+    ) -> Self {
+        let phase_function = Arc::new(Isotropic::new_with_texture(texture));
+        Self {
+            boundary,
+            field,
+            phase_function,
+        }
+    }
+
+    pub fn new_with_color(
+        boundary: Arc<dyn Hittable>,
+        field: Arc<dyn DensityField>,
+        albedo: Color,
+    ) -> Self {
+        let phase_function = Arc::new(Isotropic::new_with_color(albedo));
+        Self {
+            boundary,
+            field,
+            phase_function,
+        }
+    }
+
+    pub fn new_with_phase(
+        boundary: Arc<dyn Hittable>,
+        field: Arc<dyn DensityField>,
+        albedo: Color,
+        phase: Arc<dyn PhaseFunction>,
+    ) -> Self {
+        let phase_function = Arc::new(Anisotropic::new_with_color(albedo, phase));
+        Self {
+            boundary,
+            field,
+            phase_function,
+        }
+    }
+}
+
+impl Hittable for HeterogeneousMedium {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: &Interval) -> Option<HitRecord<'a>> {
+        let max_density = self.field.max_density();
+        if max_density <= 0.0 {
+            return None;
+        }
+        let ray_length = r.direction().length();
+
+        for segment in boundary_segments(&*self.boundary, r, ray_t) {
+            let mut t = segment.min;
+            loop {
+                t -= random_double().ln() / (max_density * ray_length);
+                if t >= segment.max {
+                    break;
+                }
+
+                let p = r.at(t);
+                if random_double() * max_density < self.field.density(&p) {
+                    return Some(HitRecord {
+                        t,
+                        p,
+                        normal: Vec3::new(1.0, 0.0, 0.0), // arbitrary
+                        front_face: true,                 // arbitrary
+                        mat: &*self.phase_function,
+                        u: 0.0,
+                        v: 0.0,
+                        dpdu: Vec3::new(0.0, 0.0, 0.0), // 没有表面，也就没有切线
+                        dpdv: Vec3::new(0.0, 0.0, 0.0),
+                        shading: ShadingCache::default(),
+                        object: self as *const Self as usize,
+                    });
+                }
+            }
+        }
+
+        None
+    }
+
+    fn bounding_box(&self) -> crate::AABB::Aabb {
+        self.boundary.bounding_box()
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: &Interval) -> Color {
+        let max_density = self.field.max_density();
+        if max_density <= 0.0 {
+            return Color::new(1.0, 1.0, 1.0);
+        }
+        let ray_length = r.direction().length();
+
+        let mut tr = 1.0;
+        for segment in boundary_segments(&*self.boundary, r, ray_t) {
+            let mut t = segment.min;
+            loop {
+                t -= random_double().ln() / (max_density * ray_length);
+                if t >= segment.max {
+                    break;
+                }
+                tr *= 1.0 - (self.field.density(&r.at(t)) / max_density).min(1.0);
+            }
+        }
+        Color::new(tr, tr, tr)
+    }
+}
@@ -3,9 +3,13 @@ use crate::interval::Interval;
 use crate::material::Material;
 use crate::ray::Ray;
 use crate::rtweekend;
-use crate::vec3::{Point3, Vec3};
+use crate::vec3::{Color, Point3, Vec3};
+use std::cell::Cell;
 use std::sync::Arc;
 
+//  阴影射线最多穿过的透光表面数，超过时视为被遮挡
+const MAX_TRANSMITTED_SURFACES: usize = 64;
+
 pub trait Hittable: Send + Sync {
     fn hit<'a>(&'a self, r: &Ray, ray_t: &Interval) -> Option<HitRecord<'a>>;
     fn bounding_box(&self) -> Aabb;
@@ -17,6 +21,35 @@ pub trait Hittable: Send + Sync {
     fn random(&self, _origin : &Point3) -> Vec3 {
         Vec3::new(1.0, 0.0, 0.0)
     }
+
+    /// 在 `time` 时刻的表面上按面积采样一点，返回该点的命中记录（法线朝外）与面积测度下的概率密度。
+    /// 从光源出发的路径用它来选择起点
+    fn sample_surface(&self, _time: f64) -> Option<(HitRecord<'_>, f64)> {
+        None
+    }
+
+    /// `sample_surface` 在 `time` 时刻采到表面点 `p` 的面积概率密度，不在表面上时为 0
+    fn surface_pdf(&self, _p : &Point3, _time: f64) -> f64 {
+        0.0
+    }
+
+    /// 射线在 `ray_t` 范围内穿过该物体的透射率：实体表面按材质的 `transmittance` 衰减
+    /// （通常完全遮挡），参与介质按消光衰减
+    fn transmittance(&self, r: &Ray, ray_t: &Interval) -> Color {
+        let mut result = Color::new(1.0, 1.0, 1.0);
+        let mut t_min = ray_t.min;
+        for _ in 0..MAX_TRANSMITTED_SURFACES {
+            let Some(rec) = self.hit(r, &Interval::new(t_min, ray_t.max)) else {
+                return result;
+            };
+            result = result * rec.mat.transmittance(r, &rec);
+            if result.max_component() <= 0.0 {
+                return result;
+            }
+            t_min = rec.t + 0.0001;
+        }
+        Color::new(0.0, 0.0, 0.0)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -28,6 +61,29 @@ pub struct HitRecord<'a> {
     pub mat: &'a dyn Material,
     pub u: f64,
     pub v: f64,
+    pub dpdu: Vec3, // 交点对 u、v 的偏导（切线），cross(dpdu, dpdv) 与外法线同向；没有参数化时为零向量
+    pub dpdv: Vec3,
+    pub shading: ShadingCache, // 材质在该交点上算出的着色法线
+    pub object: usize, // 命中的物体的标识：加入场景的顶层物体（列表或 BVH 中的元素）的地址，嵌套电介质据此区分物体
+}
+
+/// 同一个交点上会多次求 BSDF、概率密度与着色法线，着色法线（与外法线同侧）只需要算一次。
+/// 以算出它的材质的地址为键，混合材质的两层各自使用时也不会取错
+#[derive(Debug, Clone, Default)]
+pub struct ShadingCache(Cell<Option<(usize, Vec3)>>);
+
+impl ShadingCache {
+    /// `key` 对应的缓存值，没有时用 `compute` 计算并记下
+    pub fn get_or_insert_with(&self, key: usize, compute: impl FnOnce() -> Vec3) -> Vec3 {
+        match self.0.get() {
+            Some((cached, n)) if cached == key => n,
+            _ => {
+                let n = compute();
+                self.0.set(Some((key, n)));
+                n
+            }
+        }
+    }
 }
 
 impl<'a> HitRecord<'a> {
@@ -76,8 +132,9 @@ impl Hittable for HittableList {
         let mut hit_record_result: Option<HitRecord> = None;
 
         for object in &self.objects {
-            if let Some(temp_rec) = object.hit(ray, &Interval::new(ray_t.min, closest_so_far)) {
+            if let Some(mut temp_rec) = object.hit(ray, &Interval::new(ray_t.min, closest_so_far)) {
                 closest_so_far = temp_rec.t;
+                temp_rec.object = Arc::as_ptr(object) as *const () as usize;
                 hit_record_result = Some(temp_rec);
             }
         }
@@ -107,6 +164,36 @@ impl Hittable for HittableList {
         let idx = rtweekend::random_int(0, len - 1);
         self.objects[idx].random(origin)
     }
+
+    fn sample_surface(&self, time: f64) -> Option<(HitRecord<'_>, f64)> {
+        if self.objects.is_empty() {
+            return None;
+        }
+
+        let idx = rtweekend::random_int(0, self.objects.len() - 1);
+        let (rec, pdf) = self.objects[idx].sample_surface(time)?;
+        Some((rec, pdf / self.objects.len() as f64))
+    }
+
+    fn surface_pdf(&self, p: &Point3, time: f64) -> f64 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+
+        let weight = 1.0 / self.objects.len() as f64;
+        self.objects.iter().map(|object| weight * object.surface_pdf(p, time)).sum()
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: &Interval) -> Color {
+        let mut result = Color::new(1.0, 1.0, 1.0);
+        for object in &self.objects {
+            result = result * object.transmittance(r, ray_t);
+            if result.max_component() <= 0.0 {
+                break;
+            }
+        }
+        result
+    }
 }
 
 pub struct Translate {
@@ -139,6 +226,10 @@ impl Hittable for Translate {
                 mat: rec.mat,
                 u: rec.u,
                 v: rec.v,
+                dpdu: rec.dpdu,
+                dpdv: rec.dpdv,
+                shading: ShadingCache::default(),
+                object: rec.object,
             })
         } else {
             None
@@ -148,6 +239,21 @@ impl Hittable for Translate {
     fn bounding_box(&self) -> Aabb {
         self.bbox.clone()
     }
+
+    fn sample_surface(&self, time: f64) -> Option<(HitRecord<'_>, f64)> {
+        let (mut rec, pdf) = self.object.sample_surface(time)?;
+        rec.p += self.offset;
+        Some((rec, pdf))
+    }
+
+    fn surface_pdf(&self, p: &Point3, time: f64) -> f64 {
+        self.object.surface_pdf(&(*p - self.offset), time)
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: &Interval) -> Color {
+        let offset_r = Ray::new(*r.origin() - self.offset, *r.direction(), r.time());
+        self.object.transmittance(&offset_r, ray_t)
+    }
 }
 
 pub struct RotateY {
@@ -204,6 +310,24 @@ impl RotateY {
             bbox: Aabb::from_points(min, max),
         }
     }
+
+    //  把物体空间中的点或向量旋转回世界空间
+    fn to_world(&self, v: &Vec3) -> Vec3 {
+        Vec3::new(
+            self.cos_theta * v.x() + self.sin_theta * v.z(),
+            v.y(),
+            -self.sin_theta * v.x() + self.cos_theta * v.z(),
+        )
+    }
+
+    //  把世界空间中的点或向量旋转到物体空间
+    fn to_object(&self, v: &Vec3) -> Vec3 {
+        Vec3::new(
+            self.cos_theta * v.x() - self.sin_theta * v.z(),
+            v.y(),
+            self.sin_theta * v.x() + self.cos_theta * v.z(),
+        )
+    }
 }
 
 impl Hittable for RotateY {
@@ -222,16 +346,8 @@ impl Hittable for RotateY {
         let rotate_r = Ray::new(origin, direction, r.time());
 
         if let Some(rec) = self.object.hit(&rotate_r, ray_t) {
-            let p = Point3::new(
-                self.cos_theta * rec.p.x() + self.sin_theta * rec.p.z(),
-                rec.p.y(),
-                -self.sin_theta * rec.p.x() + self.cos_theta * rec.p.z(),
-            );
-            let normal = Point3::new(
-                self.cos_theta * rec.normal.x() + self.sin_theta * rec.normal.z(),
-                rec.normal.y(),
-                -self.sin_theta * rec.normal.x() + self.cos_theta * rec.normal.z(),
-            );
+            let p = self.to_world(&rec.p);
+            let normal = self.to_world(&rec.normal);
             Some(HitRecord {
                 t: rec.t,
                 p,
@@ -240,6 +356,10 @@ impl Hittable for RotateY {
                 mat: rec.mat,
                 u: rec.u,
                 v: rec.v,
+                dpdu: self.to_world(&rec.dpdu),
+                dpdv: self.to_world(&rec.dpdv),
+                shading: ShadingCache::default(),
+                object: rec.object,
             })
         } else {
             None
@@ -249,4 +369,22 @@ impl Hittable for RotateY {
     fn bounding_box(&self) -> Aabb {
         self.bbox.clone()
     }
+
+    fn sample_surface(&self, time: f64) -> Option<(HitRecord<'_>, f64)> {
+        let (mut rec, pdf) = self.object.sample_surface(time)?;
+        rec.p = self.to_world(&rec.p);
+        rec.normal = self.to_world(&rec.normal);
+        rec.dpdu = self.to_world(&rec.dpdu);
+        rec.dpdv = self.to_world(&rec.dpdv);
+        Some((rec, pdf))
+    }
+
+    fn surface_pdf(&self, p: &Point3, time: f64) -> f64 {
+        self.object.surface_pdf(&self.to_object(p), time)
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: &Interval) -> Color {
+        let rotate_r = Ray::new(self.to_object(r.origin()), self.to_object(r.direction()), r.time());
+        self.object.transmittance(&rotate_r, ray_t)
+    }
 }
@@ -0,0 +1,105 @@
+//! # `integrator.rs` 模块说明
+//!
+//! 渲染时可以选择的光传输算法，以及各个积分器共用的小工具。
+//!
+//! - `PathTracing`：默认的单向路径追踪（`Camera::ray_color`），光源与 BSDF 混合采样
+//! - `Bidirectional`：双向路径追踪（`bdpt.rs`），连接相机子路径与光源子路径并用 MIS 加权
+//! - `PhotonMapping`：光子映射（`photon_map.rs`），焦散与间接光照由光子密度估计得到
+//! - `LightTracing`：光源追踪（`light_tracer.rs`），从光源出发并把每个顶点连接到镜头上
+//! - `Metropolis`：主样本空间 Metropolis 光传输（`mlt.rs`），在路径追踪器的随机数空间中做随机游走
+
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::medium_stack::MediumStack;
+use crate::mlt::MltOptions;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::rtweekend::{self, random_double};
+use crate::vec3::{Color, Point3, Vec3};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Integrator {
+    PathTracing,
+    Bidirectional,
+    //  `photons` 为发射的光子总数，`radius` 为密度估计的搜索半径
+    PhotonMapping { photons: usize, radius: f64 },
+    LightTracing,
+    Metropolis(MltOptions),
+}
+
+/// 两点之间的透射率：被实体遮挡返回黑色，穿过参与介质时按消光衰减，
+/// 两点位于吸收性电介质 `media` 内部时再按其吸收衰减
+pub fn visibility(world: &dyn Hittable, p0: &Point3, p1: &Point3, time: f64, media: MediumStack) -> Color {
+    let direction = *p1 - *p0;
+    let distance = direction.length();
+    if distance <= 0.002 {
+        return Color::new(1.0, 1.0, 1.0);
+    }
+
+    //  两端各留出 0.001 的距离，避免与端点所在表面自交
+    let eps = 0.001 / distance;
+    let mut r = Ray::new(*p0, direction, time);
+    r.media = media;
+    r.medium_transmittance(1.0) * world.transmittance(&r, &Interval::new(eps, 1.0 - eps))
+}
+
+/// 按给定的入射射线重新确定命中记录的法线朝向，
+/// 用于在同一个表面点上以另一条入射方向计算 BSDF 或概率密度
+pub fn reoriented<'a>(rec: &HitRecord<'a>, r_in: &Ray) -> HitRecord<'a> {
+    let outward_normal: Vec3 = if rec.front_face {
+        rec.normal
+    } else {
+        -rec.normal
+    };
+    let mut oriented = rec.clone();
+    oriented.set_face_normal(r_in, &outward_normal);
+    oriented
+}
+
+/// 从光源出发的路径沿 `r_in` 到达 `rec`、散射到 `scattered` 时的 f·cosθ。
+/// 着色法线与几何法线不同时 BSDF 对辐亮度与重要性的传输不再对称，
+/// 需要乘以伴随修正 |wi·ns|·|wo·ng| / (|wi·ng|·|wo·ns|)，wi 指向光源一侧，wo 为散射方向（Veach 1997, §5.3）
+pub fn adjoint_scattering_value(r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+    let value = rec.mat.scattering_value(r_in, rec, scattered);
+    if rec.mat.is_volumetric() {
+        return value;
+    }
+    let ns = rec.mat.shading_normal(rec);
+    let wi = -*r_in.direction();
+    let wo = *scattered.direction();
+    let denominator = Vec3::dot(&wi, &rec.normal).abs() * Vec3::dot(&wo, &ns).abs();
+    if denominator <= 0.0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+    value * (Vec3::dot(&wi, &ns).abs() * Vec3::dot(&wo, &rec.normal).abs() / denominator)
+}
+
+/// 光源表面点 `rec` 朝 `target` 方向发出的辐亮度
+pub fn emitted_toward(rec: &HitRecord, target: &Point3, time: f64) -> Color {
+    let r_in = Ray::new(*target, rec.p - *target, time);
+    let oriented = reoriented(rec, &r_in);
+    rec.mat
+        .emitted(&r_in, &oriented, oriented.u, oriented.v, &oriented.p)
+}
+
+/// 从光源表面点 `rec` 按余弦分布采样发射方向，返回方向与立体角概率密度。
+/// 双面发光的表面以相同概率选择两侧
+pub fn sample_emission(rec: &HitRecord) -> (Vec3, f64) {
+    let normal = if rec.mat.is_two_sided_emitter() && random_double() < 0.5 {
+        -rec.normal
+    } else {
+        rec.normal
+    };
+    let direction = Onb::new(normal).transform(&Vec3::random_cosine_direction());
+    (direction, emission_pdf(rec, &direction))
+}
+
+/// `sample_emission` 从光源表面点 `rec` 发射到方向 `direction` 的立体角概率密度
+pub fn emission_pdf(rec: &HitRecord, direction: &Vec3) -> f64 {
+    let cosine = Vec3::dot(&Vec3::unit_vector(direction), &rec.normal);
+    if rec.mat.is_two_sided_emitter() {
+        cosine.abs() / (2.0 * rtweekend::PI_F64)
+    } else {
+        cosine.max(0.0) / rtweekend::PI_F64
+    }
+}
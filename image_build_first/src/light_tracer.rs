@@ -0,0 +1,135 @@
+//! # `light_tracer.rs` 模块说明
+//!
+//! 光源追踪（light tracing / particle tracing）：路径从 `lights` 的表面出发，
+//! 每到达一个非镜面顶点就连接到镜头上随机采样的一点，把贡献累加（splat）到所在的像素上。
+//!
+//! - 共追踪 `sample_per_pixel × 像素数` 条光源路径，每个像素的结果是所有贡献之和除以路径总数
+//! - 相机无法被射线击中，因此经过镜面后才到达相机的路径（例如透过玻璃看到的物体）不会被计入；
+//!   反过来，焦散这类从光源经镜面到达漫反射表面的路径是这种方法最擅长的
+//! - 可以作为 `Camera::ray_color` 的对照：对不含镜面顶点的路径，两者应收敛到相同的结果
+
+use crate::camera::Camera;
+use crate::hittable::Hittable;
+use crate::integrator::{adjoint_scattering_value, emitted_toward, sample_emission, visibility};
+use crate::interval::Interval;
+use crate::medium_stack::MediumStack;
+use crate::ray::Ray;
+use crate::rtweekend::random_double;
+use crate::vec3::{Color, Vec3};
+
+use rayon::prelude::*;
+use std::sync::Mutex;
+
+//  每个并行任务追踪的路径数，完成后一次性累加到帧缓冲
+const PATH_BATCH: usize = 10_000;
+
+/// 追踪全部光源路径，返回按行排列的像素颜色
+pub fn render(cam: &Camera, world: &dyn Hittable, lights: &dyn Hittable) -> Vec<Color> {
+    let pixel_count = cam.image_width * cam.image_height();
+    let path_count = cam.sample_per_pixel.max(1) * pixel_count;
+    let framebuffer = Mutex::new(vec![Color::new(0.0, 0.0, 0.0); pixel_count]);
+
+    let batches = path_count.div_ceil(PATH_BATCH);
+    (0..batches).into_par_iter().for_each(|batch| {
+        let count = PATH_BATCH.min(path_count - batch * PATH_BATCH);
+        let mut splats = Vec::new();
+        for _ in 0..count {
+            trace_path(cam, world, lights, &mut splats);
+        }
+
+        let mut fb = framebuffer.lock().unwrap();
+        for (idx, color) in splats {
+            fb[idx] += color;
+        }
+    });
+
+    let scale = 1.0 / path_count as f64;
+    framebuffer
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|color| scale * color)
+        .collect()
+}
+
+fn trace_path(
+    cam: &Camera,
+    world: &dyn Hittable,
+    lights: &dyn Hittable,
+    splats: &mut Vec<(usize, Color)>,
+) {
+    let time = random_double();
+    let Some((rec, pdf_pos)) = lights.sample_surface(time) else {
+        return;
+    };
+    if pdf_pos <= 0.0 {
+        return;
+    }
+
+    //  光源表面直接被相机看到
+    if let Some((idx, lens, importance)) = cam.connect_to_lens(&rec.p) {
+        let d = lens - rec.p;
+        let distance_squared = d.length_squared();
+        let cos_light = Vec3::dot(&rec.normal, &d).abs() / distance_squared.sqrt();
+        let le = emitted_toward(&rec, &lens, time);
+        let contribution = le * cos_light * importance / (pdf_pos * distance_squared);
+        if contribution.max_component() > 0.0 {
+            splats.push((idx, contribution * visibility(world, &rec.p, &lens, time, MediumStack::new())));
+        }
+    }
+
+    //  按余弦分布发射：beta = Le·cosθ / (pdf_pos·pdf_dir)
+    let (direction, pdf_dir) = sample_emission(&rec);
+    if pdf_dir <= 0.0 {
+        return;
+    }
+    let cos_light = Vec3::dot(&Vec3::unit_vector(&direction), &rec.normal).abs();
+    let mut beta = emitted_toward(&rec, &(rec.p + direction), time) * cos_light / (pdf_pos * pdf_dir);
+    let mut r = Ray::new(rec.p, direction, time);
+
+    for _ in 0..cam.max_depth {
+        if beta.max_component() <= 0.0 {
+            break;
+        }
+        let Some(rec) = world.hit(&r, &Interval::new(0.001, f64::INFINITY)) else {
+            break;
+        };
+        beta = beta * r.medium_transmittance(rec.t);
+        let Some(srec) = rec.mat.scatter(&r, &rec) else {
+            break;
+        };
+
+        if let Some(pdf) = srec.pdf_ptr.as_ref() {
+            if let Some((idx, lens, importance)) = cam.connect_to_lens(&rec.p) {
+                let to_lens = Ray::new(rec.p, lens - rec.p, time);
+                let distance_squared = to_lens.direction().length_squared();
+                let contribution = beta * adjoint_scattering_value(&r, &rec, &to_lens) * importance
+                    / distance_squared;
+                if contribution.max_component() > 0.0 {
+                    splats.push((idx, contribution * visibility(world, &rec.p, &lens, time, r.media)));
+                }
+            }
+
+            let direction = pdf.generate();
+            let pdf_value = pdf.value(&direction);
+            if pdf_value <= 0.0 {
+                break;
+            }
+            let scattered = r.scattered(rec.p, direction);
+            let new_beta = beta * adjoint_scattering_value(&r, &rec, &scattered) / pdf_value;
+
+            //  俄罗斯轮盘：按吞吐量的变化决定路径是否继续
+            let survive = (new_beta.max_component() / beta.max_component()).min(1.0);
+            if random_double() >= survive {
+                break;
+            }
+            beta = new_beta / survive;
+            r = scattered;
+        } else if let Some(pdf_ray) = srec.skip_pdf_ray {
+            beta = beta * srec.attenuation;
+            r = pdf_ray;
+        } else {
+            break;
+        }
+    }
+}
@@ -22,13 +22,38 @@ pub mod sphere;
 pub mod texture;
 pub mod onb;
 pub mod pdf;
+pub mod sky;
+pub mod integrator;
+pub mod bdpt;
+pub mod photon_map;
+pub mod light_tracer;
+pub mod mlt;
+pub mod density;
+pub mod heterogeneous_medium;
+pub mod phase;
+pub mod chromatic_medium;
+pub mod medium_stack;
+pub mod spectrum;
+pub mod dispersion;
+pub mod microfacet;
+pub mod principled;
+pub mod coated;
+pub mod blend;
+pub mod cutout;
+pub mod merl;
+pub mod subsurface;
+pub mod normal_map;
 use crate::camera::Camera;
 use crate::material::{Dielectric, DiffuseLight, Lambertian, Metal, EmptyMaterial};
 
 use crate::bvh::BvhNode;
 use crate::hittable::{HittableList, RotateY, Translate, Hittable};
 use crate::quad::Quad;
+use crate::sky::Sky;
 use crate::sphere::Sphere;
+use crate::density::{PerlinDensity, VoxelGrid};
+use crate::heterogeneous_medium::HeterogeneousMedium;
+use crate::phase::HenyeyGreenstein;
 use crate::texture::{CheckerTexture, ImageTexture, NoiseTexture};
 
 use std::time::Instant;
@@ -65,6 +90,7 @@ fn main() -> std::io::Result<()> {
         6 => simple_light(),
         7 => cornell_box(),
         8 => cornell_smoke(),
+        10 => cornell_clouds(),
         9 => final_scene(800, 10000, 40),
         _ => final_scene(400, 250, 4),
     }?;
@@ -159,9 +185,9 @@ fn bouncing_spheres() -> Result<(), std::io::Error> {
         material3,
     )));
 
-    let empty_material = Arc::new(EmptyMaterial {});
-    let quad_lights = Quad::new(Point3::new(343.0, 554.0, 332.0), Vec3::new(-130.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -105.0), empty_material);
-    let lights : Arc<dyn Hittable + Send + Sync> = Arc::new(quad_lights);
+    //  太阳高度角 35°，晴朗天空
+    let sky = Arc::new(Sky::new(35.0, 60.0, 3.0));
+    let lights : Arc<dyn Hittable + Send + Sync> = sky.clone();
 
     let bvh_root = Arc::new(BvhNode::new_from_list(&world));
     let world = bvh_root;
@@ -173,7 +199,7 @@ fn bouncing_spheres() -> Result<(), std::io::Error> {
     let mut cam = Camera::new(aspect_ratio, image_width);
     cam.sample_per_pixel = 500;
     cam.max_depth = 50;
-    cam.background = Color::new(0.70, 0.80, 1.00);
+    cam.sky = Some(sky);
     cam.vfov = 20.0;
     cam.lookfrom = Point3::new(13.0, 2.0, 3.0);
     cam.lookat = Point3::new(0.0, 0.0, 0.0);
@@ -468,7 +494,7 @@ fn cornell_box() -> std::io::Result<()> {
         Point3::new(343.0, 554.0, 332.0),
         Vec3::new(-130.0, 0.0, 0.0),
         Vec3::new(0.0, 0.0, -105.0),
-        lignt,
+        lignt.clone(),
     )));
     world.add(Arc::new(Quad::new(
         Point3::new(0.0, 0.0, 0.0),
@@ -513,9 +539,12 @@ fn cornell_box() -> std::io::Result<()> {
     // let box2 = Arc::new(Translate::new(box2, Vec3::new(130.0, 0.0, 65.0)));
     // world.add(box2);
 
+    //  玻璃球只用来引导路径追踪的方向采样，从光源出发的积分器只使用天花板上的灯
     let empty_material = Arc::new(EmptyMaterial {});
+    let ceiling_light : Arc<dyn Hittable + Send + Sync> =
+        Arc::new(Quad::new(Point3::new(343.0, 554.0, 332.0), Vec3::new(-130.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -105.0), lignt));
     let mut lights = HittableList::new();
-    lights.add(Arc::new(Quad::new(Point3::new(343.0, 554.0, 332.0), Vec3::new(-130.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -105.0), empty_material.clone())));
+    lights.add(ceiling_light.clone());
     lights.add(Arc::new(Sphere::new_stationary(Point3::new(190.0, 90.0, 190.0), 90.0, empty_material)));
     // let quad_lights = Quad::new(Point3::new(343.0, 554.0, 332.0), Vec3::new(-130.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -105.0), empty_material);
     // let lights : Arc<dyn Hittable + Send + Sync> = Arc::new(quad_lights);
@@ -536,6 +565,7 @@ fn cornell_box() -> std::io::Result<()> {
     cam.lookfrom = Point3::new(278.0, 278.0, -800.0);
     cam.lookat = Point3::new(278.0, 278.0, 0.0);
     cam.vup = Vec3::new(0.0, 1.0, 0.0);
+    cam.emitters = Some(ceiling_light);
 
     cam.defocus_angle = 0.0;
 
@@ -570,7 +600,7 @@ fn cornell_smoke() -> Result<(), std::io::Error> {
         Point3::new(113.0, 554.0, 127.0),
         Vec3::new(330.0, 0.0, 0.0),
         Vec3::new(0.0, 0.0, 305.0),
-        lignt,
+        lignt.clone(),
     )));
     world.add(Arc::new(Quad::new(
         Point3::new(0.0, 555.0, 0.0),
@@ -617,8 +647,81 @@ fn cornell_smoke() -> Result<(), std::io::Error> {
         Color::new(1.0, 1.0, 1.0),
     )));
 
-    let empty_material = Arc::new(EmptyMaterial {});
-    let quad_lights = Quad::new(Point3::new(113.0, 554.0, 127.0), Vec3::new(330.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 305.0), empty_material);
+    let quad_lights = Quad::new(Point3::new(113.0, 554.0, 127.0), Vec3::new(330.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 305.0), lignt);
+    let lights : Arc<dyn Hittable + Send + Sync> = Arc::new(quad_lights);
+
+    let bvh_root = Arc::new(BvhNode::new_from_list(&world));
+    let world = bvh_root;
+
+    let aspect_ratio: f64 = 1.0;
+    let image_width: usize = 600;
+
+    //  camera
+    let mut cam = Camera::new(aspect_ratio, image_width);
+    cam.sample_per_pixel = 200;
+    cam.max_depth = 50;
+    cam.background = Color::new(0.0, 0.0, 0.0);
+    cam.vfov = 40.0;
+    cam.lookfrom = Point3::new(278.0, 278.0, -800.0);
+    cam.lookat = Point3::new(278.0, 278.0, 0.0);
+    cam.vup = Vec3::new(0.0, 1.0, 0.0);
+
+    cam.defocus_angle = 0.0;
+
+    let stdout = stdout(); // 获取 stdout 句柄
+    let writer = BufWriter::new(stdout);
+    cam.initialize();
+    cam.render(world, writer, lights)?;
+    Ok(())
+}
+
+fn cornell_clouds() -> Result<(), std::io::Error> {
+    let mut world = HittableList::new();
+
+    let red = Arc::new(Lambertian::new(Color::new(0.65, 0.05, 0.05)));
+    let white = Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
+    let green = Arc::new(Lambertian::new(Color::new(0.12, 0.45, 0.15)));
+    let light = Arc::new(DiffuseLight::from_color(Color::new(7.0, 7.0, 7.0)));
+
+    world.add(Arc::new(Quad::new(Point3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), Vec3::new(0.0, 0.0, 555.0), green)));
+    world.add(Arc::new(Quad::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), Vec3::new(0.0, 0.0, 555.0), red)));
+    world.add(Arc::new(Quad::new(Point3::new(113.0, 554.0, 127.0), Vec3::new(330.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 305.0), light.clone())));
+    world.add(Arc::new(Quad::new(Point3::new(0.0, 555.0, 0.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 555.0), white.clone())));
+    world.add(Arc::new(Quad::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 555.0), white.clone())));
+    world.add(Arc::new(Quad::new(Point3::new(0.0, 0.0, 555.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), white.clone())));
+
+    //  两个相互重叠的球组成的非凸边界，内部是 Perlin 湍流密度
+    let mut cloud = HittableList::new();
+    cloud.add(Arc::new(Sphere::new_stationary(Point3::new(200.0, 330.0, 300.0), 110.0, white.clone())));
+    cloud.add(Arc::new(Sphere::new_stationary(Point3::new(350.0, 360.0, 300.0), 90.0, white.clone())));
+    world.add(Arc::new(HeterogeneousMedium::new_with_phase(
+        Arc::new(cloud),
+        Arc::new(PerlinDensity::new(0.05, 0.02)),
+        Color::new(1.0, 1.0, 1.0),
+        Arc::new(HenyeyGreenstein::new(0.6)),
+    )));
+
+    //  体素网格：中心浓、边缘淡的一团烟
+    let n = 16;
+    let mut data = Vec::with_capacity(n * n * n);
+    for z in 0..n {
+        for y in 0..n {
+            for x in 0..n {
+                let c = (n as f64 - 1.0) / 2.0;
+                let d = Vec3::new(x as f64 - c, y as f64 - c, z as f64 - c).length() / c;
+                data.push(0.03 * (1.0 - d).max(0.0));
+            }
+        }
+    }
+    let smoke_min = Point3::new(330.0, 0.0, 100.0);
+    let smoke_max = Point3::new(500.0, 170.0, 270.0);
+    world.add(Arc::new(HeterogeneousMedium::new_with_color(
+        Quad::make_box(&smoke_min, &smoke_max, white),
+        Arc::new(VoxelGrid::new(n, n, n, data, smoke_min, smoke_max)),
+        Color::new(0.8, 0.8, 0.8),
+    )));
+
+    let quad_lights = Quad::new(Point3::new(113.0, 554.0, 127.0), Vec3::new(330.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 305.0), light);
     let lights : Arc<dyn Hittable + Send + Sync> = Arc::new(quad_lights);
 
     let bvh_root = Arc::new(BvhNode::new_from_list(&world));
@@ -680,7 +783,7 @@ fn final_scene(
         Point3::new(123.0, 554.0, 147.0),
         Vec3::new(300.0, 0.0, 0.0),
         Vec3::new(0.0, 0.0, 265.0),
-        light,
+        light.clone(),
     )));
 
     let center1 = Point3::new(400.0, 400.0, 200.0);
@@ -759,8 +862,7 @@ fn final_scene(
         Vec3::new(-100.0, 270.0, 395.0),
     )));
 
-    let empty_material = Arc::new(EmptyMaterial {});
-    let quad_lights = Quad::new(Point3::new(123.0, 554.0, 147.0), Vec3::new(300.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 265.0), empty_material);
+    let quad_lights = Quad::new(Point3::new(123.0, 554.0, 147.0), Vec3::new(300.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 265.0), light);
     let lights : Arc<dyn Hittable + Send + Sync> = Arc::new(quad_lights);
 
     let bvh_root = Arc::new(BvhNode::new_from_list(&world));
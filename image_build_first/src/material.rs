@@ -2,7 +2,15 @@ use std::fmt::Debug;
 use std::sync::Arc;
 
 use crate::hittable::HitRecord;
+use crate::dispersion::Dispersion;
+use crate::medium_stack::MediumEntry;
+use crate::microfacet::{
+    fresnel_dielectric, rough_dielectric, DielectricMicrofacetPdf, Fresnel, MicrofacetPdf, TrowbridgeReitz,
+};
+use crate::onb::Onb;
+use crate::spectrum;
 use crate::pdf::{CosinePdf, Pdf, SpherePdf};
+use crate::phase::{PhaseFunction, PhasePdf};
 use crate::ray::Ray;
 use crate::rtweekend::{self, random_double};
 
@@ -13,6 +21,7 @@ pub struct  ScatterRecord {
     pub attenuation : Color,
     pub pdf_ptr: Option<Arc<dyn Pdf + Send + Sync>>,
     pub skip_pdf_ray: Option<Ray>,
+    pub null_collision: bool, //  空碰撞（参与介质、薄片透射）：`skip_pdf_ray` 沿原方向继续，不构成路径顶点
 }
 
 pub trait Material: Send + Sync + Debug {
@@ -27,6 +36,52 @@ pub trait Material: Send + Sync + Debug {
     fn scattering_pdf(&self, _r_in : &Ray, _rec : &HitRecord, _scattered : &Ray) -> f64 {
         0.0
     }
+
+    /// 给定入射与出射方向时 BSDF 与余弦项的乘积 f·cosθ。
+    /// 默认为 0，适用于只有 delta 波瓣或不散射的材质；scatter 会给出概率密度的材质必须重写，
+    /// 且不能再调用 scatter，否则求值与采样用的是两次不同的随机选择
+    fn scattering_value(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    /// scatter 在交点处给出概率密度（而不是 `skip_pdf_ray` 或 None）的概率，只由材质与入射方向决定，不做随机采样。
+    /// 镜面、玻璃、光源等只有 delta 波瓣或不散射的材质为 0，只有可求值波瓣的材质为 1，
+    /// 两者的组合（例如光滑涂层下的漫反射）介于其间。
+    /// `scattering_value` 返回的是可求值部分的 f·cosθ 除以这一概率，与 scatter 随机选中它的概率相抵消
+    fn pdf_probability(&self, _r_in: &Ray, _rec: &HitRecord) -> f64 {
+        0.0
+    }
+
+    /// 是否为参与介质中的相函数：这类散射点没有表面法线，换算面积测度时不乘余弦
+    fn is_volumetric(&self) -> bool {
+        false
+    }
+
+    /// 表面两侧是否都发光。从光源出发的路径据此决定在哪些半球内采样发射方向
+    fn is_two_sided_emitter(&self) -> bool {
+        false
+    }
+
+    /// 合成用的遮挡物（`Holdout`）：相机直接看到时该处完全透明
+    fn is_holdout(&self) -> bool {
+        false
+    }
+
+    /// 合成用的阴影接收面（`ShadowCatcher`）：相机直接看到时只输出它接收到的阴影与其他物体反射过来的光
+    fn is_shadow_catcher(&self) -> bool {
+        false
+    }
+
+    /// 着色时实际使用的法线，与 `rec.normal` 同侧。从光源出发的路径据此做伴随修正，见 `adjoint_scattering_value`
+    fn shading_normal(&self, rec: &HitRecord) -> Vec3 {
+        rec.normal
+    }
+
+    /// 阴影射线不改变方向直接穿过该表面的透射率，默认完全遮挡。
+    /// 只有 scatter 会沿原方向继续（空碰撞）的材质才需要重写，使连接与散射的结果一致
+    fn transmittance(&self, _r_in: &Ray, _rec: &HitRecord) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
 }
 
 #[derive(Debug)]
@@ -48,7 +103,11 @@ impl Lambertian {
 
 impl Material for Lambertian {
     fn scatter(&self, _r_in: &Ray, rec: &HitRecord)  -> Option<ScatterRecord>  {
-        Some(ScatterRecord { attenuation: self.tex.value(rec.u, rec.v, &rec.p), pdf_ptr: Some(Arc::new(CosinePdf::new(rec.normal))), skip_pdf_ray: None })
+        Some(ScatterRecord { attenuation: self.tex.value(rec.u, rec.v, &rec.p), pdf_ptr: Some(Arc::new(CosinePdf::new(rec.normal))), skip_pdf_ray: None, null_collision: false })
+    }
+
+    fn pdf_probability(&self, _r_in: &Ray, _rec: &HitRecord) -> f64 {
+        1.0
     }
 
     fn scattering_pdf(&self, _r_in : &Ray, rec : &HitRecord, scattered : &Ray) -> f64 {
@@ -59,38 +118,271 @@ impl Material for Lambertian {
             cos_theta / rtweekend::PI_F64
         }
     }
+
+    fn scattering_value(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.tex.value(rec.u, rec.v, &rec.p) * self.scattering_pdf(r_in, rec, scattered)
+    }
 }
 
+/// Oren-Nayar 粗糙漫反射（黏土、混凝土、布料），`sigma` 为微表面朝向的标准差（角度），为 0 时即 Lambertian。
+/// 采样与 `Lambertian` 相同，按余弦分布
+#[derive(Debug)]
+pub struct OrenNayar {
+    tex: Arc<dyn Texture + Send + Sync>,
+    sigma: Arc<dyn Texture + Send + Sync>,
+}
+
+impl OrenNayar {
+    pub fn new(albedo: Color, sigma: f64) -> Self {
+        Self {
+            tex: Arc::new(SolidColor::new(albedo)),
+            sigma: Arc::new(SolidColor::from_rgb(sigma, sigma, sigma)),
+        }
+    }
+
+    /// `sigma` 取纹理颜色的第一个分量
+    pub fn from_texture(tex: Arc<dyn Texture + Send + Sync>, sigma: Arc<dyn Texture + Send + Sync>) -> Self {
+        Self { tex, sigma }
+    }
+}
+
+impl Material for OrenNayar {
+    fn scatter(&self, _r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        Some(ScatterRecord { attenuation: self.tex.value(rec.u, rec.v, &rec.p), pdf_ptr: Some(Arc::new(CosinePdf::new(rec.normal))), skip_pdf_ray: None, null_collision: false })
+    }
+
+    fn pdf_probability(&self, _r_in: &Ray, _rec: &HitRecord) -> f64 {
+        1.0
+    }
+
+    fn scattering_pdf(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let cos_theta = Vec3::dot(&rec.normal, &Vec3::unit_vector(scattered.direction()));
+        if cos_theta < 0.0 {
+            0.0
+        } else {
+            cos_theta / rtweekend::PI_F64
+        }
+    }
+
+    fn scattering_value(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        let uvw = Onb::new(rec.normal);
+        let wo = Vec3::unit_vector(&uvw.to_local(&-*r_in.direction()));
+        let wi = Vec3::unit_vector(&uvw.to_local(scattered.direction()));
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        let sigma = rtweekend::degrees_to_radians(self.sigma.value(rec.u, rec.v, &rec.p).x());
+        let sigma2 = sigma * sigma;
+        let a = 1.0 - sigma2 / (2.0 * (sigma2 + 0.33));
+        let b = 0.45 * sigma2 / (sigma2 + 0.09);
+
+        //  max(0, cos(φi - φo))·sinα·tanβ，α、β 分别为 θi、θo 中较大与较小者
+        let sin_o = (1.0 - wo.z() * wo.z()).max(0.0).sqrt();
+        let sin_i = (1.0 - wi.z() * wi.z()).max(0.0).sqrt();
+        let max_cos = if sin_o > 1e-4 && sin_i > 1e-4 {
+            ((wi.x() * wo.x() + wi.y() * wo.y()) / (sin_i * sin_o)).max(0.0)
+        } else {
+            0.0
+        };
+        let (sin_alpha, tan_beta) = if wi.z() > wo.z() {
+            (sin_o, sin_i / wi.z())
+        } else {
+            (sin_i, sin_o / wo.z())
+        };
+
+        let factor = a + b * max_cos * sin_alpha * tan_beta;
+        self.tex.value(rec.u, rec.v, &rec.p) * (factor * wi.z() / rtweekend::PI_F64)
+    }
+}
+
+/// 金属，即微表面导体（GGX 法线分布），粗糙度为 0 时是理想镜面
 #[derive(Debug)]
 pub struct Metal {
-    pub albedo: Color,
-    pub fuzz: f64,
+    fresnel: Fresnel,
+    distribution: TrowbridgeReitz,
 }
 
 impl Metal {
+    /// 法向反射率为 `albedo` 的金属，沿用模糊金属的 `fuzz` 参数：
+    /// 半径为 `fuzz` 的随机扰动与 α = fuzz / 2 的 GGX 波瓣宽度相当
     pub fn new(albedo: Color, fuzz: f64) -> Metal {
-        Metal { albedo, fuzz }
+        Metal::rough(albedo, (0.5 * fuzz.clamp(0.0, 1.0)).sqrt())
+    }
+
+    /// 法向反射率为 `albedo` 的微表面金属（Schlick 近似），`roughness` ∈ [0, 1]
+    pub fn rough(albedo: Color, roughness: f64) -> Metal {
+        Metal {
+            fresnel: Fresnel::Schlick(albedo),
+            distribution: TrowbridgeReitz::from_roughness(roughness, roughness),
+        }
+    }
+
+    /// 复折射率为 `eta` + i`k` 的导体，各通道分别给出
+    pub fn new_complex(eta: Color, k: Color, roughness: f64) -> Metal {
+        Metal {
+            fresnel: Fresnel::Conductor { eta, k },
+            distribution: TrowbridgeReitz::from_roughness(roughness, roughness),
+        }
+    }
+
+    /// 沿切线与副切线方向的粗糙度分别为 `roughness_u`、`roughness_v`（例如拉丝金属）。
+    /// 切线方向为表面的 ∂p/∂u
+    pub fn with_anisotropic_roughness(mut self, roughness_u: f64, roughness_v: f64) -> Self {
+        self.distribution = TrowbridgeReitz::from_roughness(roughness_u, roughness_v);
+        self
+    }
+
+    //  局部坐标系：z 轴为法线，x 轴沿 ∂p/∂u，各向异性粗糙度据此对齐
+    fn frame(rec: &HitRecord) -> Onb {
+        Onb::from_normal_tangent(rec.normal, rec.dpdu)
+    }
+
+    //  常见金属在 R、G、B 三个波长（约 650、550、450 nm）处的复折射率
+    pub fn gold(roughness: f64) -> Metal {
+        Metal::new_complex(
+            Color::new(0.143, 0.374, 1.442),
+            Color::new(3.983, 2.386, 1.603),
+            roughness,
+        )
+    }
+
+    pub fn copper(roughness: f64) -> Metal {
+        Metal::new_complex(
+            Color::new(0.200, 0.924, 1.102),
+            Color::new(3.912, 2.452, 2.142),
+            roughness,
+        )
+    }
+
+    pub fn aluminium(roughness: f64) -> Metal {
+        Metal::new_complex(
+            Color::new(1.657, 0.880, 0.521),
+            Color::new(9.224, 6.270, 4.837),
+            roughness,
+        )
+    }
+
+    pub fn silver(roughness: f64) -> Metal {
+        Metal::new_complex(
+            Color::new(0.155, 0.117, 0.138),
+            Color::new(4.828, 3.122, 2.147),
+            roughness,
+        )
     }
 }
 
 impl Material for Metal {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
-        let reflected1 = Vec3::reflect(r_in.direction(), &rec.normal);
-        let reflected = Vec3::unit_vector(&reflected1) + self.fuzz * Vec3::random_unit_vector();
-        
-        Some(ScatterRecord { attenuation: self.albedo, pdf_ptr: None, skip_pdf_ray: Some(Ray::new(rec.p, reflected, r_in.time())) })
+        let unit_direction = Vec3::unit_vector(r_in.direction());
+        let cos_theta = -Vec3::dot(&unit_direction, &rec.normal);
+        if cos_theta <= 0.0 {
+            return None;
+        }
+
+        if self.distribution.is_smooth() {
+            let reflected = Vec3::reflect(&unit_direction, &rec.normal);
+            return Some(ScatterRecord {
+                attenuation: self.fresnel.evaluate(cos_theta),
+                pdf_ptr: None,
+                skip_pdf_ray: Some(r_in.scattered(rec.p, reflected)),
+                null_collision: false,
+            });
+        }
+
+        Some(ScatterRecord {
+            attenuation: self.fresnel.evaluate(cos_theta),
+            pdf_ptr: Some(Arc::new(MicrofacetPdf::with_frame(Metal::frame(rec), -unit_direction, self.distribution))),
+            skip_pdf_ray: None,
+            null_collision: false,
+        })
+    }
+
+    fn pdf_probability(&self, _r_in: &Ray, _rec: &HitRecord) -> f64 {
+        if self.distribution.is_smooth() {
+            0.0
+        } else {
+            1.0
+        }
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        MicrofacetPdf::with_frame(Metal::frame(rec), -*r_in.direction(), self.distribution).value(scattered.direction())
+    }
+
+    fn scattering_value(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        let uvw = Metal::frame(rec);
+        let wo = Vec3::unit_vector(&uvw.to_local(&-*r_in.direction()));
+        let wi = Vec3::unit_vector(&uvw.to_local(scattered.direction()));
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        //  f·cosθi = D(m)·G(wo, wi)·F(wo·m) / (4·cosθo)
+        let m = Vec3::unit_vector(&(wo + wi));
+        let d = self.distribution.d(&m);
+        let g = self.distribution.g(&wo, &wi);
+        self.fresnel.evaluate(Vec3::dot(&wo, &m)) * (d * g / (4.0 * wo.z()))
     }
 }
 
 #[derive(Debug)]
 pub struct Dielectric {
     refraction_index: f64,
+    absorption: Color, //  内部每单位长度的吸收系数，0 表示完全透明
+    priority: i32,     //  与其他电介质重叠时，优先级高的占据重叠部分
+    dispersion: Option<Dispersion>,
 }
 
 impl Dielectric {
     pub fn new(refraction_index: f64) -> Self {
         Self {
             refraction_index: refraction_index,
+            absorption: Color::new(0.0, 0.0, 0.0),
+            priority: 0,
+            dispersion: None,
+        }
+    }
+
+    /// 折射率随波长变化的色散玻璃。路径第一次遇到色散材质时均匀采样一个波长并乘上该波长的 RGB 权重，
+    /// 之后沿路径保持该波长（双向方法中相机与光源子路径各自选择）
+    pub fn new_dispersive(dispersion: Dispersion) -> Self {
+        Self {
+            refraction_index: dispersion.ior_d(),
+            absorption: Color::new(0.0, 0.0, 0.0),
+            priority: 0,
+            dispersion: Some(dispersion),
+        }
+    }
+
+    /// 内部按 Beer–Lambert 定律吸收的有色玻璃或液体：光在内部走过 `distance` 后剩下 `color`，
+    /// 因此越厚的地方颜色越深。`distance` 与 `color` 一样被截断到正值，避免吸收系数为无穷大
+    pub fn new_with_absorption(refraction_index: f64, color: Color, distance: f64) -> Self {
+        let distance = distance.max(1e-6);
+        let coefficient = |c: f64| -c.clamp(1e-6, 1.0).ln() / distance;
+        Self {
+            refraction_index,
+            absorption: Color::new(coefficient(color.x()), coefficient(color.y()), coefficient(color.z())),
+            priority: 0,
+            dispersion: None,
+        }
+    }
+
+    /// 设置嵌套时的优先级，例如装水的玻璃杯中玻璃的优先级应高于水
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// 物体 `object` 内部的介质条目
+    fn medium_entry(&self, object: usize) -> MediumEntry {
+        MediumEntry {
+            object,
+            material: self as *const Self as usize,
+            priority: self.priority,
+            ior: self.refraction_index,
+            dispersion: self.dispersion,
+            absorption: self.absorption,
         }
     }
 
@@ -104,50 +396,259 @@ impl Dielectric {
 
 impl Material for Dielectric {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
-        let ri = if rec.front_face {
-            1.0 / self.refraction_index
+        //  色散材质在路径还没有波长时选定一个：光谱模式下取第一个 hero 波长，
+        //  否则随机采样并把该波长的颜色计入权重
+        let mut spectral_weight = Color::new(1.0, 1.0, 1.0);
+        let mut wavelength = r_in.wavelength;
+        if self.dispersion.is_some() {
+            wavelength.get_or_insert_with(|| match r_in.wavelengths {
+                Some(lambdas) => lambdas[0],
+                None => {
+                    let (lambda, weight) = spectrum::sample_wavelength();
+                    spectral_weight = weight;
+                    lambda
+                }
+            });
+        }
+        let entry = self.medium_entry(rec.object);
+
+        //  界面前后射线所在的介质；没有记录就从内部射到表面时，视为一直在该物体内部
+        let mut before = r_in.media;
+        if !rec.front_face && !before.contains(entry.object) {
+            before.push(entry);
+        }
+        let mut after = before;
+        if rec.front_face {
+            after.push(entry);
         } else {
-            self.refraction_index
-        };
+            after.remove(entry.object);
+        }
+
+        //  介质内的吸收由积分器在每一段上计算（`Ray::medium_transmittance`）
+        let (current, next) = (before.top(), after.top());
+        let attenuation = spectral_weight;
+
+        let n1 = current.map_or(1.0, |medium| medium.ior_at(wavelength));
+        let n2 = next.map_or(1.0, |medium| medium.ior_at(wavelength));
+
+        //  起作用的介质没有变化（例如进入优先级更低的物体），不是真正的界面，直接穿过
+        if current.map(|medium| medium.object) == next.map(|medium| medium.object) {
+            let mut pass = r_in.scattered(rec.p, *r_in.direction());
+            pass.media = after;
+            pass.wavelength = wavelength;
+            return Some(ScatterRecord { attenuation, pdf_ptr: None, skip_pdf_ray: Some(pass), null_collision: false });
+        }
 
+        let ri = n1 / n2;
         let uint_direction = Vec3::unit_vector(r_in.direction());
         let cos_theta = Vec3::dot(&(-uint_direction), &rec.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
-        let direction =
+        //  反射留在原来的介质中，折射则进入界面另一侧
+        let (direction, media) =
             if ri * sin_theta > 1.0 || Dielectric::reflectance(cos_theta, ri) > random_double() {
-                Vec3::reflect(&uint_direction, &rec.normal)
+                (Vec3::reflect(&uint_direction, &rec.normal), before)
             } else {
-                Vec3::refract(&uint_direction, rec.normal, ri)
+                (Vec3::refract(&uint_direction, rec.normal, ri), after)
             };
+        let mut scattered = r_in.scattered(rec.p, direction);
+        scattered.media = media;
+        scattered.wavelength = wavelength;
 
-        Some(ScatterRecord { attenuation: Color::new(1.0, 1.0, 1.0), pdf_ptr: None, skip_pdf_ray: Some(Ray::new(rec.p,direction, r_in.time())) })
+        Some(ScatterRecord { attenuation, pdf_ptr: None, skip_pdf_ray: Some(scattered), null_collision: false })
     }
 }
 
+/// 薄片电介质（窗玻璃、肥皂泡）：两个界面靠得很近，透射光不发生偏折，用一个 `Quad` 即可表示。
+/// 片内的多次反射按几何级数求和，总反射率 R' = 2R / (1 + R)，其余全部透射
+#[derive(Debug)]
+pub struct ThinDielectric {
+    refraction_index: f64,
+}
+
+impl ThinDielectric {
+    pub fn new(refraction_index: f64) -> Self {
+        Self { refraction_index }
+    }
+
+    //  入射方向与法线夹角余弦为 `cos_theta` 时薄片的总反射率
+    fn reflectance(&self, cos_theta: f64) -> f64 {
+        let r = fresnel_dielectric(cos_theta, self.refraction_index);
+        if r < 1.0 { 2.0 * r / (1.0 + r) } else { 1.0 }
+    }
+
+    fn cos_theta(r_in: &Ray, rec: &HitRecord) -> f64 {
+        Vec3::dot(&(-Vec3::unit_vector(r_in.direction())), &rec.normal).min(1.0)
+    }
+}
+
+impl Material for ThinDielectric {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        //  反射是一个镜面顶点；透射沿原方向继续，标记为空碰撞，不产生路径顶点，
+        //  与 `transmittance` 让连接射线以 1 - R' 穿过薄片的估计一致
+        let reflected = self.reflectance(ThinDielectric::cos_theta(r_in, rec)) > random_double();
+        let direction = if reflected {
+            Vec3::reflect(&Vec3::unit_vector(r_in.direction()), &rec.normal)
+        } else {
+            *r_in.direction()
+        };
+        Some(ScatterRecord {
+            attenuation: Color::new(1.0, 1.0, 1.0),
+            pdf_ptr: None,
+            skip_pdf_ray: Some(r_in.scattered(rec.p, direction)),
+            null_collision: !reflected,
+        })
+    }
+
+    fn transmittance(&self, r_in: &Ray, rec: &HitRecord) -> Color {
+        let t = 1.0 - self.reflectance(ThinDielectric::cos_theta(r_in, rec));
+        Color::new(t, t, t)
+    }
+}
+
+/// 磨砂玻璃：基于微表面反射与透射的粗糙电介质，粗糙度由纹理的第一个分量给出。
+/// 界面两侧按射线是否从外部射入区分，不参与 `Dielectric` 的嵌套介质与吸收
+#[derive(Debug)]
+pub struct RoughDielectric {
+    refraction_index: f64,
+    roughness: Arc<dyn Texture>,
+}
+
+impl RoughDielectric {
+    pub fn new(refraction_index: f64, roughness: f64) -> Self {
+        Self {
+            refraction_index,
+            roughness: Arc::new(SolidColor::new(Color::new(roughness, roughness, roughness))),
+        }
+    }
+
+    pub fn new_with_texture(refraction_index: f64, roughness: Arc<dyn Texture>) -> Self {
+        Self {
+            refraction_index,
+            roughness,
+        }
+    }
+
+    fn distribution(&self, rec: &HitRecord) -> TrowbridgeReitz {
+        let roughness = self.roughness.value(rec.u, rec.v, &rec.p).x();
+        TrowbridgeReitz::from_roughness(roughness, roughness)
+    }
+
+    //  法线背面与正面的折射率之比
+    fn eta(&self, rec: &HitRecord) -> f64 {
+        if rec.front_face {
+            self.refraction_index
+        } else {
+            1.0 / self.refraction_index
+        }
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let distribution = self.distribution(rec);
+        let unit_direction = Vec3::unit_vector(r_in.direction());
+        let eta = self.eta(rec);
+
+        if distribution.is_smooth() {
+            let cos_theta = Vec3::dot(&(-unit_direction), &rec.normal).min(1.0);
+            let direction = if fresnel_dielectric(cos_theta, eta) > random_double() {
+                Vec3::reflect(&unit_direction, &rec.normal)
+            } else {
+                Vec3::refract(&unit_direction, rec.normal, 1.0 / eta)
+            };
+            return Some(ScatterRecord {
+                attenuation: Color::new(1.0, 1.0, 1.0),
+                pdf_ptr: None,
+                skip_pdf_ray: Some(r_in.scattered(rec.p, direction)),
+                null_collision: false,
+            });
+        }
+
+        Some(ScatterRecord {
+            attenuation: Color::new(1.0, 1.0, 1.0),
+            pdf_ptr: Some(Arc::new(DielectricMicrofacetPdf::new(rec.normal, -unit_direction, eta, distribution))),
+            skip_pdf_ray: None,
+            null_collision: false,
+        })
+    }
+
+    fn pdf_probability(&self, _r_in: &Ray, rec: &HitRecord) -> f64 {
+        if self.distribution(rec).is_smooth() { 0.0 } else { 1.0 }
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        DielectricMicrofacetPdf::new(rec.normal, -*r_in.direction(), self.eta(rec), self.distribution(rec))
+            .value(scattered.direction())
+    }
+
+    fn scattering_value(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        let uvw = Onb::new(rec.normal);
+        let wo = Vec3::unit_vector(&uvw.to_local(&-*r_in.direction()));
+        let wi = Vec3::unit_vector(&uvw.to_local(scattered.direction()));
+        let (value, _) = rough_dielectric(&self.distribution(rec), &wo, &wi, self.eta(rec));
+        Color::new(value, value, value)
+    }
+}
+
+/// 漫射面光源。发光颜色由纹理给出，可以另外设置：
+/// - 亮度倍数 `strength`，与颜色分开调整
+/// - 双面发光，默认只有正面发光
+/// - 遮罩纹理，取第一个分量与发光相乘，用于灯带、招牌等局部发光的图案
 #[derive(Debug)]
 pub struct DiffuseLight {
     tex: Arc<dyn Texture>,
+    strength: f64,
+    two_sided: bool,
+    mask: Option<Arc<dyn Texture>>,
 }
 
 impl DiffuseLight {
     pub fn from_texture(tex: Arc<dyn Texture>) -> Self {
-        Self { tex }
+        Self {
+            tex,
+            strength: 1.0,
+            two_sided: false,
+            mask: None,
+        }
     }
 
     pub fn from_color(c: Color) -> Self {
-        Self {
-            tex: Arc::new(SolidColor::new(c)),
-        }
+        Self::from_texture(Arc::new(SolidColor::new(c)))
+    }
+
+    /// 色温为 `kelvin` 的黑体颜色，亮度为 1，用 `with_strength` 调整
+    pub fn from_temperature(kelvin: f64) -> Self {
+        Self::from_color(spectrum::blackbody_rgb(kelvin))
+    }
+
+    pub fn with_strength(mut self, strength: f64) -> Self {
+        self.strength = strength;
+        self
+    }
+
+    pub fn with_two_sided(mut self, two_sided: bool) -> Self {
+        self.two_sided = two_sided;
+        self
+    }
+
+    pub fn with_mask(mut self, mask: Arc<dyn Texture>) -> Self {
+        self.mask = Some(mask);
+        self
     }
 }
 
 impl Material for DiffuseLight {
     fn emitted(&self, _r_in: &Ray, rec: &HitRecord, u: f64, v: f64, p: &Point3) -> Color {
-        if !rec.front_face {
+        if !rec.front_face && !self.two_sided {
             return Color::new(0.0, 0.0, 0.0);
         }
-        self.tex.as_ref().value(u, v, p)
+        let mask = self.mask.as_ref().map_or(1.0, |mask| mask.value(u, v, p).x().clamp(0.0, 1.0));
+        self.strength * mask * self.tex.as_ref().value(u, v, p)
+    }
+
+    fn is_two_sided_emitter(&self) -> bool {
+        self.two_sided
     }
 }
 
@@ -170,12 +671,75 @@ impl Isotropic {
 
 impl Material for Isotropic {
     fn scatter(&self, _r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
-        Some(ScatterRecord { attenuation: self.tex.value(rec.u, rec.v, &rec.p), pdf_ptr: Some(Arc::new(SpherePdf::new())), skip_pdf_ray: None })
+        Some(ScatterRecord { attenuation: self.tex.value(rec.u, rec.v, &rec.p), pdf_ptr: Some(Arc::new(SpherePdf::new())), skip_pdf_ray: None, null_collision: false })
+    }
+
+    fn pdf_probability(&self, _r_in: &Ray, _rec: &HitRecord) -> f64 {
+        1.0
     }
 
     fn scattering_pdf(&self, _r_in : &Ray, _rec : &HitRecord, _scattered : &Ray) -> f64 {
         1.0 / (4.0 * rtweekend::PI_F64)
     }
+
+    fn scattering_value(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.tex.value(rec.u, rec.v, &rec.p) * self.scattering_pdf(r_in, rec, scattered)
+    }
+
+    fn is_volumetric(&self) -> bool {
+        true
+    }
+}
+
+/// 使用任意相函数的参与介质材质，按相函数对散射方向做重要性采样
+#[derive(Debug)]
+pub struct Anisotropic {
+    tex: Arc<dyn Texture>,
+    phase: Arc<dyn PhaseFunction>,
+}
+
+impl Anisotropic {
+    pub fn new_with_texture(tex: Arc<dyn Texture>, phase: Arc<dyn PhaseFunction>) -> Self {
+        Self { tex, phase }
+    }
+
+    pub fn new_with_color(albedo: Color, phase: Arc<dyn PhaseFunction>) -> Self {
+        Self {
+            tex: Arc::new(SolidColor::new(albedo)),
+            phase,
+        }
+    }
+}
+
+impl Material for Anisotropic {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        Some(ScatterRecord {
+            attenuation: self.tex.value(rec.u, rec.v, &rec.p),
+            pdf_ptr: Some(Arc::new(PhasePdf::new(*r_in.direction(), self.phase.clone()))),
+            skip_pdf_ray: None,
+            null_collision: false,
+        })
+    }
+
+    fn pdf_probability(&self, _r_in: &Ray, _rec: &HitRecord) -> f64 {
+        1.0
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, _rec: &HitRecord, scattered: &Ray) -> f64 {
+        let cos_theta = Vec3::dot(
+            &Vec3::unit_vector(r_in.direction()),
+            &Vec3::unit_vector(scattered.direction()),
+        );
+        self.phase.evaluate(cos_theta)
+    }
+
+    fn scattering_value(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.tex.value(rec.u, rec.v, &rec.p) * self.scattering_pdf(r_in, rec, scattered)
+    }
+
+    fn is_volumetric(&self) -> bool {
+        true
+    }
 }
 
 #[derive(Debug)]
@@ -193,4 +757,59 @@ impl Material for EmptyMaterial {
     fn scattering_pdf(&self, _r_in : &Ray, _rec : &HitRecord, _scattered : &Ray) -> f64 {
         0.0
     }
-}
\ No newline at end of file
+}
+
+/// 合成用的遮挡物：代表照片中本来就有、会挡住渲染物体的东西。
+/// 相机直接看到它时 `Camera::rgba_output` 输出完全透明，对其他射线则是不反射也不发光的黑色物体
+#[derive(Debug)]
+pub struct Holdout;
+
+impl Material for Holdout {
+    fn is_holdout(&self) -> bool {
+        true
+    }
+}
+
+/// 合成用的阴影接收面：代表照片中的地面或桌面。对其他射线它是普通的漫反射表面，
+/// 相机直接看到它时 `Camera::rgba_output` 的不透明度为它接收到的光照相对于没有遮挡时减少的比例，
+/// 颜色只包含其他物体反射到它上面的光，见 `Camera::render`
+#[derive(Debug)]
+pub struct ShadowCatcher {
+    diffuse: Lambertian,
+}
+
+impl ShadowCatcher {
+    pub fn new(albedo: Color) -> Self {
+        Self {
+            diffuse: Lambertian::new(albedo),
+        }
+    }
+
+    pub fn from_texture(tex: Arc<dyn Texture + Send + Sync>) -> Self {
+        Self {
+            diffuse: Lambertian::from_texture(tex),
+        }
+    }
+}
+
+impl Material for ShadowCatcher {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        self.diffuse.scatter(r_in, rec)
+    }
+
+    fn pdf_probability(&self, _r_in: &Ray, _rec: &HitRecord) -> f64 {
+        1.0
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.diffuse.scattering_pdf(r_in, rec, scattered)
+    }
+
+    fn scattering_value(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.diffuse.scattering_value(r_in, rec, scattered)
+    }
+
+    fn is_shadow_catcher(&self) -> bool {
+        true
+    }
+}
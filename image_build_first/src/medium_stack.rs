@@ -0,0 +1,248 @@
+//! # `medium_stack.rs` 模块说明
+//!
+//! 射线当前所在的（可能相互嵌套的）电介质，用于计算界面两侧的相对折射率
+//! （Schmidt & Budge 2002, "Simple Nested Dielectrics in Ray Traced Images"）。
+//!
+//! - 射线进入电介质时压栈，离开时出栈，栈随射线沿路径传递（见 `Ray::scattered`）
+//! - 每种介质有一个优先级，重叠区域属于优先级最高的介质；
+//!   例如装水的玻璃杯，水与玻璃的几何体可以略微重叠，由更高优先级的玻璃占据重叠部分
+//! - 只有当最高优先级的介质发生变化时才是真正的界面，否则射线直接穿过
+//! - 条目按命中的物体（`HitRecord::object`）区分，多个物体共用同一个材质也互不影响
+//!
+//! `MediumStack` 只是一个 4 字节的句柄，实际内容登记在全局表中：场景里出现过的栈组合很少，
+//! 登记后不再释放，这样 `Ray` 仍然可以廉价地 `Copy`，句柄也可以在线程之间传递。
+//! 嵌套超过 `MAX_NESTED_MEDIA` 层时最内层会被忽略。
+
+use crate::dispersion::Dispersion;
+use crate::vec3::Color;
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+
+pub const MAX_NESTED_MEDIA: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MediumEntry {
+    pub object: usize,   //  所在物体的标识，用来在离开时找到对应的条目
+    pub material: usize, //  所属材质的地址，与 `object` 一起决定条目的内容
+    pub priority: i32,
+    pub ior: f64,                       //  d 线（或唯一的）折射率
+    pub dispersion: Option<Dispersion>, //  色散材质按路径的波长计算折射率
+    pub absorption: Color,              //  每单位长度的吸收系数
+}
+
+impl MediumEntry {
+    const EMPTY: Self = Self {
+        object: 0,
+        material: 0,
+        priority: 0,
+        ior: 1.0,
+        dispersion: None,
+        absorption: Color { e: [0.0, 0.0, 0.0] },
+    };
+
+    /// 路径波长为 `wavelength` 时的折射率
+    pub fn ior_at(&self, wavelength: Option<f64>) -> f64 {
+        match (self.dispersion, wavelength) {
+            (Some(dispersion), Some(lambda)) => dispersion.ior(lambda),
+            _ => self.ior,
+        }
+    }
+}
+
+/// 登记表的键：栈中各条目的 (物体, 材质)
+type Key = ([(usize, usize); MAX_NESTED_MEDIA], usize);
+
+#[derive(Clone, Copy)]
+struct Entries {
+    entries: [MediumEntry; MAX_NESTED_MEDIA],
+    len: usize,
+}
+
+impl Entries {
+    fn key(&self) -> Key {
+        let mut key = [(0, 0); MAX_NESTED_MEDIA];
+        for (slot, entry) in key.iter_mut().zip(&self.entries[..self.len]) {
+            *slot = (entry.object, entry.material);
+        }
+        (key, self.len)
+    }
+}
+
+struct Registry {
+    stacks: Vec<Entries>, //  下标即句柄，0 号为空栈
+    index: HashMap<Key, u32>,
+}
+
+fn registry() -> &'static RwLock<Registry> {
+    static REGISTRY: OnceLock<RwLock<Registry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let empty = Entries {
+            entries: [MediumEntry::EMPTY; MAX_NESTED_MEDIA],
+            len: 0,
+        };
+        RwLock::new(Registry {
+            index: HashMap::from([(empty.key(), 0)]),
+            stacks: vec![empty],
+        })
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MediumStack(u32);
+
+impl MediumStack {
+    pub const fn new() -> Self {
+        Self(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    fn entries(&self) -> Entries {
+        registry().read().unwrap().stacks[self.0 as usize]
+    }
+
+    fn intern(entries: Entries) -> Self {
+        let key = entries.key();
+        if let Some(&handle) = registry().read().unwrap().index.get(&key) {
+            return Self(handle);
+        }
+        let mut registry = registry().write().unwrap();
+        let handle = match registry.index.get(&key) {
+            Some(&handle) => handle,
+            None => {
+                let handle = registry.stacks.len() as u32;
+                registry.stacks.push(entries);
+                registry.index.insert(key, handle);
+                handle
+            }
+        };
+        Self(handle)
+    }
+
+    pub fn contains(&self, object: usize) -> bool {
+        let stack = self.entries();
+        stack.entries[..stack.len].iter().any(|entry| entry.object == object)
+    }
+
+    /// 当前起作用的介质：优先级最高者，优先级相同时取最后进入的
+    pub fn top(&self) -> Option<MediumEntry> {
+        let stack = self.entries();
+        //  max_by_key 在相等时返回最后一个
+        stack.entries[..stack.len].iter().max_by_key(|entry| entry.priority).copied()
+    }
+
+    /// 在当前起作用的介质中走过 `distance` 后剩下的比例（Beer–Lambert 定律）
+    pub fn transmittance(&self, distance: f64) -> Color {
+        let sigma = match self.top() {
+            Some(medium) => medium.absorption,
+            None => return Color::new(1.0, 1.0, 1.0),
+        };
+        //  不吸收的分量在 distance 为无穷大时也保持 1
+        let channel = |s: f64| if s > 0.0 { (-s * distance).exp() } else { 1.0 };
+        Color::new(channel(sigma.x()), channel(sigma.y()), channel(sigma.z()))
+    }
+
+    pub fn push(&mut self, entry: MediumEntry) {
+        let mut stack = self.entries();
+        if stack.len < MAX_NESTED_MEDIA {
+            stack.entries[stack.len] = entry;
+            stack.len += 1;
+            *self = Self::intern(stack);
+        }
+    }
+
+    /// 移除最后进入的 `object` 对应的条目
+    pub fn remove(&mut self, object: usize) {
+        let mut stack = self.entries();
+        if let Some(idx) = stack.entries[..stack.len].iter().rposition(|entry| entry.object == object) {
+            stack.entries.copy_within(idx + 1..stack.len, idx);
+            stack.len -= 1;
+            stack.entries[stack.len] = MediumEntry::EMPTY;
+            *self = Self::intern(stack);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(object: usize, priority: i32) -> MediumEntry {
+        MediumEntry {
+            object,
+            material: 1,
+            priority,
+            ior: 1.0 + object as f64 / 10.0,
+            ..MediumEntry::EMPTY
+        }
+    }
+
+    #[test]
+    fn push_and_remove_return_to_the_same_stack() {
+        let mut stack = MediumStack::new();
+        stack.push(entry(11, 0));
+        let glass = stack;
+        stack.push(entry(12, 0));
+        assert!(stack.contains(11) && stack.contains(12));
+        assert_eq!(stack.top().unwrap().object, 12);
+
+        stack.remove(12);
+        assert_eq!(stack, glass);
+        stack.remove(11);
+        assert!(stack.is_empty());
+        //  不在栈中的物体不影响栈
+        stack.remove(13);
+        assert_eq!(stack, MediumStack::new());
+    }
+
+    #[test]
+    fn objects_sharing_a_material_are_separate_entries() {
+        let mut stack = MediumStack::new();
+        stack.push(entry(21, 0));
+        stack.push(entry(22, 0));
+        //  先离开外层物体时内层的条目保留
+        stack.remove(21);
+        assert!(!stack.contains(21));
+        assert_eq!(stack.top().unwrap().object, 22);
+    }
+
+    #[test]
+    fn top_is_highest_priority_then_latest() {
+        let mut stack = MediumStack::new();
+        stack.push(entry(31, 2));
+        stack.push(entry(32, 1));
+        assert_eq!(stack.top().unwrap().object, 31);
+        stack.push(entry(33, 2));
+        assert_eq!(stack.top().unwrap().object, 33);
+        stack.remove(33);
+        assert_eq!(stack.top().unwrap().object, 31);
+    }
+
+    #[test]
+    fn transmittance_follows_the_active_medium() {
+        let mut stack = MediumStack::new();
+        assert_eq!(stack.transmittance(f64::INFINITY), Color::new(1.0, 1.0, 1.0));
+        stack.push(MediumEntry {
+            absorption: Color::new(0.5, 0.0, 2.0),
+            ..entry(51, 0)
+        });
+        let tr = stack.transmittance(2.0);
+        assert!((tr.x() - (-1.0f64).exp()).abs() < 1e-12);
+        assert_eq!(tr.y(), 1.0);
+        assert!((tr.z() - (-4.0f64).exp()).abs() < 1e-12);
+        //  逃逸射线：吸收的分量为 0，不吸收的分量仍为 1
+        assert_eq!(stack.transmittance(f64::INFINITY), Color::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn overflowing_entries_are_ignored() {
+        let mut stack = MediumStack::new();
+        for object in 41..41 + MAX_NESTED_MEDIA + 1 {
+            stack.push(entry(object, 0));
+        }
+        assert!(!stack.contains(41 + MAX_NESTED_MEDIA));
+        assert_eq!(stack.top().unwrap().object, 40 + MAX_NESTED_MEDIA);
+    }
+}
@@ -0,0 +1,432 @@
+//! # `merl.rs` 模块说明
+//!
+//! 读取 MERL BRDF 数据库的 `.binary` 文件，把实测的各向同性材质（油漆、金属、塑料）
+//! 与解析材质放在同一个场景中渲染。
+//!
+//! - 文件开头是三个 i32 维度 (90, 90, 180)，之后依次是 R、G、B 三块 f64 数据，
+//!   按半角/差角参数化 (θh, θd, φd) 存储，θh 方向按平方根非均匀划分
+//! - 三个通道分别乘上 1/1500、1.15/1500、1.66/1500 换算为 BRDF 值，负值表示缺失数据，按 0 处理
+//! - 重要性采样使用加载时建立的表：对若干个出射天顶角，按亮度 f·cosθi 把入射半球划分为
+//!   (θi, Δφ) 网格上的分段常数分布；采样时再与余弦分布混合，避免表格过粗时漏掉窄的高光
+
+use std::fs;
+use std::sync::Arc;
+
+use crate::hittable::HitRecord;
+use crate::material::{Material, ScatterRecord};
+use crate::onb::Onb;
+use crate::pdf::{CosinePdf, Pdf, WeightedMixturePdf};
+use crate::ray::Ray;
+use crate::rtweekend::{self, random_double};
+use crate::vec3::{Color, Vec3};
+
+const THETA_HALF_RES: usize = 90;
+const THETA_DIFF_RES: usize = 90;
+const PHI_DIFF_RES: usize = 180;
+const SAMPLES: usize = THETA_HALF_RES * THETA_DIFF_RES * PHI_DIFF_RES;
+
+const RED_SCALE: f64 = 1.0 / 1500.0;
+const GREEN_SCALE: f64 = 1.15 / 1500.0;
+const BLUE_SCALE: f64 = 1.66 / 1500.0;
+
+//  采样表的分辨率：出射天顶角、入射天顶角、入射与出射的方位角差
+const TABLE_THETA_O: usize = 32;
+const TABLE_THETA_I: usize = 64;
+const TABLE_PHI: usize = 64;
+
+//  采样时选择余弦分布的概率
+const COSINE_WEIGHT: f64 = 0.1;
+
+/// 分段常数的一维离散分布
+struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    total: f64,
+}
+
+impl Distribution1D {
+    //  权重全为 0 时退化为均匀分布
+    fn new(func: Vec<f64>) -> Self {
+        let total: f64 = func.iter().sum();
+        let func = if total > 0.0 { func } else { vec![1.0; func.len()] };
+        let total: f64 = func.iter().sum();
+        let mut cdf = Vec::with_capacity(func.len() + 1);
+        cdf.push(0.0);
+        for value in &func {
+            cdf.push(cdf.last().unwrap() + value / total);
+        }
+        Self { func, cdf, total }
+    }
+
+    //  按离散概率选择一个下标
+    fn sample(&self, u: f64) -> usize {
+        (self.cdf.partition_point(|&c| c <= u) - 1).min(self.func.len() - 1)
+    }
+
+    fn probability(&self, index: usize) -> f64 {
+        self.func[index] / self.total
+    }
+}
+
+//  某个出射天顶角下的入射方向分布：先选 θi 行，再选 Δφ 列
+struct Slice {
+    theta: Distribution1D,
+    phi: Vec<Distribution1D>,
+}
+
+/// 加载后的实测 BRDF 数据与采样表
+struct MerlData {
+    brdf: Vec<f32>,
+    slices: Vec<Slice>,
+    albedo: Vec<Color>, //  每个出射天顶角下的方向反照率
+}
+
+impl MerlData {
+    fn load(filename: &str) -> Option<Self> {
+        Self::from_bytes(&fs::read(filename).ok()?)
+    }
+
+    //  维度必须逐个等于 (90, 90, 180)，只比较乘积会让顺序颠倒的文件通过检查
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != 12 + 3 * SAMPLES * 8 {
+            return None;
+        }
+        let dims: Vec<i32> = bytes[..12]
+            .chunks_exact(4)
+            .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        if dims != [THETA_HALF_RES as i32, THETA_DIFF_RES as i32, PHI_DIFF_RES as i32] {
+            return None;
+        }
+        let brdf = bytes[12..]
+            .chunks_exact(8)
+            .map(|b| f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]) as f32)
+            .collect();
+
+        let mut data = Self {
+            brdf,
+            slices: Vec::new(),
+            albedo: Vec::new(),
+        };
+        data.build_table();
+        Some(data)
+    }
+
+    //  局部坐标系（法线为 z 轴）中入射方向 `wi`、出射方向 `wo` 上的 BRDF 值
+    fn lookup(&self, wi: &Vec3, wo: &Vec3) -> Color {
+        let half = Vec3::unit_vector(&(*wi + *wo));
+        let theta_half = half.z().clamp(-1.0, 1.0).acos();
+        let phi_half = half.y().atan2(half.x());
+
+        //  把入射方向转到半角向量为 z 轴的坐标系：先绕法线转 -φh，再绕副法线转 -θh
+        let (sin_phi, cos_phi) = phi_half.sin_cos();
+        let x = wi.x() * cos_phi + wi.y() * sin_phi;
+        let y = -wi.x() * sin_phi + wi.y() * cos_phi;
+        let (sin_theta, cos_theta) = theta_half.sin_cos();
+        let diff = Vec3::new(x * cos_theta - wi.z() * sin_theta, y, x * sin_theta + wi.z() * cos_theta);
+        let theta_diff = diff.z().clamp(-1.0, 1.0).acos();
+        let mut phi_diff = diff.y().atan2(diff.x());
+        if phi_diff < 0.0 {
+            phi_diff += rtweekend::PI_F64;
+        }
+
+        let index = phi_diff_index(phi_diff)
+            + theta_diff_index(theta_diff) * PHI_DIFF_RES
+            + theta_half_index(theta_half) * PHI_DIFF_RES * THETA_DIFF_RES;
+        let channel = |c: usize, scale: f64| (self.brdf[index + c * SAMPLES] as f64 * scale).max(0.0);
+        Color::new(channel(0, RED_SCALE), channel(1, GREEN_SCALE), channel(2, BLUE_SCALE))
+    }
+
+    fn build_table(&mut self) {
+        let half_pi = rtweekend::PI_F64 / 2.0;
+        let d_phi = rtweekend::PI_F64 / TABLE_PHI as f64;
+        for j in 0..TABLE_THETA_O {
+            let theta_o = (j as f64 + 0.5) / TABLE_THETA_O as f64 * half_pi;
+            let wo = Vec3::new(theta_o.sin(), 0.0, theta_o.cos());
+
+            let mut rows = Vec::with_capacity(TABLE_THETA_I);
+            let mut phi = Vec::with_capacity(TABLE_THETA_I);
+            let mut albedo = Color::new(0.0, 0.0, 0.0);
+            for a in 0..TABLE_THETA_I {
+                let solid_angle = theta_cell_solid_angle(a) * d_phi;
+                let theta_i = (a as f64 + 0.5) / TABLE_THETA_I as f64 * half_pi;
+                let mut weights = Vec::with_capacity(TABLE_PHI);
+                for b in 0..TABLE_PHI {
+                    let delta_phi = (b as f64 + 0.5) * d_phi;
+                    let wi = Vec3::new(
+                        theta_i.sin() * delta_phi.cos(),
+                        theta_i.sin() * delta_phi.sin(),
+                        theta_i.cos(),
+                    );
+                    //  Δφ 只取 [0, π]，另一半对称，方向反照率乘 2
+                    let value = self.lookup(&wi, &wo) * (wi.z() * solid_angle);
+                    albedo += 2.0 * value;
+                    weights.push(0.2126 * value.x() + 0.7152 * value.y() + 0.0722 * value.z());
+                }
+                rows.push(weights.iter().sum());
+                phi.push(Distribution1D::new(weights));
+            }
+            self.slices.push(Slice {
+                theta: Distribution1D::new(rows),
+                phi,
+            });
+            self.albedo.push(albedo);
+        }
+    }
+
+    //  出射天顶角余弦为 `cos_theta_o` 时使用的采样表
+    fn slice_index(cos_theta_o: f64) -> usize {
+        let theta_o = cos_theta_o.clamp(0.0, 1.0).acos();
+        ((theta_o / (rtweekend::PI_F64 / 2.0) * TABLE_THETA_O as f64) as usize).min(TABLE_THETA_O - 1)
+    }
+}
+
+fn theta_half_index(theta_half: f64) -> usize {
+    if theta_half <= 0.0 {
+        return 0;
+    }
+    let degrees = theta_half / (rtweekend::PI_F64 / 2.0) * THETA_HALF_RES as f64;
+    ((degrees * THETA_HALF_RES as f64).sqrt() as usize).min(THETA_HALF_RES - 1)
+}
+
+fn theta_diff_index(theta_diff: f64) -> usize {
+    ((theta_diff / (rtweekend::PI_F64 / 2.0) * THETA_DIFF_RES as f64) as usize).min(THETA_DIFF_RES - 1)
+}
+
+fn phi_diff_index(phi_diff: f64) -> usize {
+    ((phi_diff / rtweekend::PI_F64 * PHI_DIFF_RES as f64) as usize).min(PHI_DIFF_RES - 1)
+}
+
+//  第 a 行 θi 网格的上下边界余弦之差，即单位方位角内的立体角
+fn theta_cell_solid_angle(a: usize) -> f64 {
+    let half_pi = rtweekend::PI_F64 / 2.0;
+    let theta_lo = a as f64 / TABLE_THETA_I as f64 * half_pi;
+    let theta_hi = (a + 1) as f64 / TABLE_THETA_I as f64 * half_pi;
+    theta_lo.cos() - theta_hi.cos()
+}
+
+/// 按采样表选择入射方向的概率密度
+struct MerlPdf {
+    uvw: Onb,
+    phi_o: f64,
+    slice: usize,
+    data: Arc<MerlData>,
+}
+
+impl MerlPdf {
+    fn new(normal: Vec3, wo: Vec3, data: Arc<MerlData>) -> Self {
+        let uvw = Onb::new(normal);
+        let wo = Vec3::unit_vector(&uvw.to_local(&wo));
+        Self {
+            uvw,
+            phi_o: wo.y().atan2(wo.x()),
+            slice: MerlData::slice_index(wo.z()),
+            data,
+        }
+    }
+}
+
+impl Pdf for MerlPdf {
+    fn value(&self, direction: &Vec3) -> f64 {
+        let wi = Vec3::unit_vector(&self.uvw.to_local(direction));
+        if wi.z() <= 0.0 {
+            return 0.0;
+        }
+        let theta_i = wi.z().min(1.0).acos();
+        let a = ((theta_i / (rtweekend::PI_F64 / 2.0) * TABLE_THETA_I as f64) as usize).min(TABLE_THETA_I - 1);
+        let mut delta_phi = (wi.y().atan2(wi.x()) - self.phi_o).abs();
+        if delta_phi > rtweekend::PI_F64 {
+            delta_phi = 2.0 * rtweekend::PI_F64 - delta_phi;
+        }
+        let b = ((delta_phi / rtweekend::PI_F64 * TABLE_PHI as f64) as usize).min(TABLE_PHI - 1);
+
+        let slice = &self.data.slices[self.slice];
+        let probability = slice.theta.probability(a) * slice.phi[a].probability(b);
+        let d_phi = rtweekend::PI_F64 / TABLE_PHI as f64;
+        probability / (2.0 * theta_cell_solid_angle(a) * d_phi)
+    }
+
+    fn generate(&self) -> Vec3 {
+        let slice = &self.data.slices[self.slice];
+        let a = slice.theta.sample(random_double());
+        let b = slice.phi[a].sample(random_double());
+
+        //  网格内按立体角均匀采样：cosθ 均匀，Δφ 均匀，并随机取 Δφ 的正负
+        let half_pi = rtweekend::PI_F64 / 2.0;
+        let cos_hi = (a as f64 / TABLE_THETA_I as f64 * half_pi).cos();
+        let cos_lo = ((a + 1) as f64 / TABLE_THETA_I as f64 * half_pi).cos();
+        let cos_theta = cos_lo + random_double() * (cos_hi - cos_lo);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let delta_phi = (b as f64 + random_double()) / TABLE_PHI as f64 * rtweekend::PI_F64;
+        let phi = if random_double() < 0.5 {
+            self.phi_o + delta_phi
+        } else {
+            self.phi_o - delta_phi
+        };
+        self.uvw
+            .transform(&Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta))
+    }
+}
+
+/// MERL 格式的实测各向同性 BRDF。文件无法读取时报告错误，材质不反射任何光
+pub struct MerlBrdf {
+    data: Option<Arc<MerlData>>,
+}
+
+impl std::fmt::Debug for MerlBrdf {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MerlBrdf").field("loaded", &self.data.is_some()).finish()
+    }
+}
+
+impl MerlBrdf {
+    pub fn new(filename: &str) -> Self {
+        let data = MerlData::load(filename).map(Arc::new);
+        if data.is_none() {
+            eprintln!("ERROR: Could not load MERL BRDF file '{}'.\n", filename);
+        }
+        Self { data }
+    }
+
+    fn local_directions(r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> (Vec3, Vec3) {
+        let uvw = Onb::new(rec.normal);
+        let wo = Vec3::unit_vector(&uvw.to_local(&-*r_in.direction()));
+        let wi = Vec3::unit_vector(&uvw.to_local(scattered.direction()));
+        (wi, wo)
+    }
+
+    fn pdf(&self, data: &Arc<MerlData>, r_in: &Ray, rec: &HitRecord) -> WeightedMixturePdf {
+        let table: Arc<dyn Pdf + Send + Sync> = Arc::new(MerlPdf::new(rec.normal, -*r_in.direction(), data.clone()));
+        let cosine: Arc<dyn Pdf + Send + Sync> = Arc::new(CosinePdf::new(rec.normal));
+        WeightedMixturePdf::new(vec![(1.0 - COSINE_WEIGHT, table), (COSINE_WEIGHT, cosine)])
+    }
+}
+
+impl Material for MerlBrdf {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let data = self.data.as_ref()?;
+        let cos_theta = -Vec3::dot(&Vec3::unit_vector(r_in.direction()), &rec.normal);
+        if cos_theta <= 0.0 {
+            return None;
+        }
+        Some(ScatterRecord {
+            attenuation: data.albedo[MerlData::slice_index(cos_theta)],
+            pdf_ptr: Some(Arc::new(self.pdf(data, r_in, rec))),
+            skip_pdf_ray: None,
+            null_collision: false,
+        })
+    }
+
+    fn pdf_probability(&self, _r_in: &Ray, _rec: &HitRecord) -> f64 {
+        if self.data.is_some() { 1.0 } else { 0.0 }
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        match self.data.as_ref() {
+            Some(data) => self.pdf(data, r_in, rec).value(scattered.direction()),
+            None => 0.0,
+        }
+    }
+
+    fn scattering_value(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        let Some(data) = self.data.as_ref() else {
+            return Color::new(0.0, 0.0, 0.0);
+        };
+        let (wi, wo) = MerlBrdf::local_directions(r_in, rec, scattered);
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        data.lookup(&wi, &wo) * wi.z()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //  按 (θh, θd, φd) 下标的格子中心构造一对方向，φh 取 0
+    fn directions(half: usize, diff: usize, phi: usize) -> (Vec3, Vec3) {
+        let half_pi = rtweekend::PI_F64 / 2.0;
+        let theta_half = (half as f64 + 0.5).powi(2) / THETA_HALF_RES as f64 / THETA_HALF_RES as f64 * half_pi;
+        let theta_diff = (diff as f64 + 0.5) / THETA_DIFF_RES as f64 * half_pi;
+        let phi_diff = (phi as f64 + 0.5) / PHI_DIFF_RES as f64 * rtweekend::PI_F64;
+
+        let d = Vec3::new(
+            theta_diff.sin() * phi_diff.cos(),
+            theta_diff.sin() * phi_diff.sin(),
+            theta_diff.cos(),
+        );
+        let (sin_h, cos_h) = theta_half.sin_cos();
+        let wi = Vec3::new(d.x() * cos_h + d.z() * sin_h, d.y(), -d.x() * sin_h + d.z() * cos_h);
+        let h = Vec3::new(sin_h, 0.0, cos_h);
+        let wo = 2.0 * Vec3::dot(&wi, &h) * h - wi;
+        (wi, wo)
+    }
+
+    fn file(dims: [i32; 3]) -> Vec<u8> {
+        let mut bytes: Vec<u8> = dims.iter().flat_map(|d| d.to_le_bytes()).collect();
+        for c in 0..3 {
+            for i in 0..SAMPLES {
+                bytes.extend_from_slice(&((i + c) as f64).to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn lookup_round_trip() {
+        let data = MerlData::from_bytes(&file([90, 90, 180])).unwrap();
+        for &(half, diff, phi) in &[(1, 3, 5), (20, 45, 90), (60, 30, 170), (89, 10, 0)] {
+            let (wi, wo) = directions(half, diff, phi);
+            let index = phi + diff * PHI_DIFF_RES + half * PHI_DIFF_RES * THETA_DIFF_RES;
+            let expected = Color::new(
+                index as f64 * RED_SCALE,
+                (index + 1) as f64 * GREEN_SCALE,
+                (index + 2) as f64 * BLUE_SCALE,
+            );
+            let value = data.lookup(&wi, &wo);
+            for c in 0..3 {
+                assert!((value[c] - expected[c]).abs() < 1e-9, "{:?} != {:?}", value, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_permuted_or_truncated_files() {
+        assert!(MerlData::from_bytes(&file([180, 90, 90])).is_none());
+        assert!(MerlData::from_bytes(&file([90, 180, 90])).is_none());
+        assert!(MerlData::from_bytes(&file([-90, -90, 180])).is_none());
+        let bytes = file([90, 90, 180]);
+        assert!(MerlData::from_bytes(&bytes[..bytes.len() - 8]).is_none());
+    }
+
+    #[test]
+    fn distribution_probability_and_sampling() {
+        let dist = Distribution1D::new(vec![1.0, 0.0, 3.0]);
+        assert_eq!(dist.probability(0), 0.25);
+        assert_eq!(dist.probability(1), 0.0);
+        assert_eq!(dist.probability(2), 0.75);
+        assert_eq!(dist.sample(0.0), 0);
+        assert_eq!(dist.sample(0.25), 2);
+        assert_eq!(dist.sample(0.999_999), 2);
+
+        //  均匀的 u 落入各个下标的比例等于离散概率
+        let n = 100_000;
+        let mut counts = [0usize; 3];
+        for i in 0..n {
+            counts[dist.sample((i as f64 + 0.5) / n as f64)] += 1;
+        }
+        for (index, &count) in counts.iter().enumerate() {
+            assert!((count as f64 / n as f64 - dist.probability(index)).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn zero_distribution_is_uniform() {
+        let dist = Distribution1D::new(vec![0.0; 4]);
+        for index in 0..4 {
+            assert_eq!(dist.probability(index), 0.25);
+        }
+        assert_eq!(dist.sample(0.6), 2);
+    }
+}
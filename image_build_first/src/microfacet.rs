@@ -0,0 +1,401 @@
+//! # `microfacet.rs` 模块说明
+//!
+//! 微表面模型：粗糙表面看成大量朝向随机的理想镜面小平面，小平面法线 m 的分布决定高光的形状。
+//! - `TrowbridgeReitz`：GGX / Trowbridge-Reitz 法线分布，沿切线与副切线方向可以取不同的 α（各向异性）
+//! - 遮挡与阴影使用高度相关的 Smith 函数 G(wo, wi) = 1 / (1 + Λ(wo) + Λ(wi))
+//! - 只采样从出射方向可见的小平面法线（Heitz 2018, "Sampling the GGX Distribution of Visible Normals"），
+//!   比直接按 D(m) 采样的方差小得多
+//! - `Fresnel`：Schlick 近似或复折射率 η + ik 的导体菲涅尔项，逐通道计算
+//! - `rough_dielectric`：粗糙电介质的反射与透射（Walter et al. 2007, "Microfacet Models for Refraction
+//!   through Rough Surfaces"），透射方向用广义半程向量 m ∝ η·wi + wo 求出对应的小平面
+//!
+//! 这里的向量都在局部坐标系中：z 轴为宏观法线，x、y 轴分别为切线与副切线方向。
+
+use crate::onb::Onb;
+use crate::pdf::Pdf;
+use crate::rtweekend::{self, random_double};
+use crate::vec3::{Color, Vec3};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrowbridgeReitz {
+    alpha_x: f64,
+    alpha_y: f64,
+}
+
+impl TrowbridgeReitz {
+    /// 由感知上线性的粗糙度 ∈ [0, 1] 构造，α = roughness²
+    pub fn from_roughness(roughness_u: f64, roughness_v: f64) -> Self {
+        Self {
+            alpha_x: roughness_u.clamp(0.0, 1.0).powi(2),
+            alpha_y: roughness_v.clamp(0.0, 1.0).powi(2),
+        }
+    }
+
+    /// α 很小时分布接近 δ 函数，按理想镜面处理
+    pub fn is_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < 1e-3
+    }
+
+    /// 法线分布函数 D(m)
+    pub fn d(&self, m: &Vec3) -> f64 {
+        if m.z() <= 0.0 {
+            return 0.0;
+        }
+        let x = m.x() / self.alpha_x;
+        let y = m.y() / self.alpha_y;
+        let t = x * x + y * y + m.z() * m.z();
+        1.0 / (rtweekend::PI_F64 * self.alpha_x * self.alpha_y * t * t)
+    }
+
+    //  Smith 遮挡函数中的辅助函数 Λ(w)
+    fn lambda(&self, w: &Vec3) -> f64 {
+        let cos2 = w.z() * w.z();
+        if cos2 <= 0.0 {
+            return f64::INFINITY;
+        }
+        let ax = self.alpha_x * w.x();
+        let ay = self.alpha_y * w.y();
+        let tan2_alpha2 = (ax * ax + ay * ay) / cos2;
+        0.5 * (-1.0 + (1.0 + tan2_alpha2).sqrt())
+    }
+
+    /// 单方向的遮挡函数 G1(w)
+    pub fn g1(&self, w: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// 高度相关的遮挡-阴影函数 G(wo, wi)
+    pub fn g(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// 从 `wo` 方向看过去可见的小平面法线的分布 D_wo(m) = G1(wo)·max(0, wo·m)·D(m) / cosθo
+    pub fn visible_d(&self, wo: &Vec3, m: &Vec3) -> f64 {
+        if wo.z() <= 0.0 {
+            return 0.0;
+        }
+        self.g1(wo) * Vec3::dot(wo, m).max(0.0) * self.d(m) / wo.z()
+    }
+
+    /// 按 D_wo(m) 采样一个小平面法线，要求 wo 位于上半球
+    pub fn sample_visible_normal(&self, wo: &Vec3) -> Vec3 {
+        //  拉伸到 α = 1 的半球上
+        let vh = Vec3::unit_vector(&Vec3::new(self.alpha_x * wo.x(), self.alpha_y * wo.y(), wo.z()));
+        let len2 = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if len2 > 0.0 {
+            Vec3::new(-vh.y(), vh.x(), 0.0) / len2.sqrt()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = Vec3::cross(&vh, &t1);
+
+        //  在投影后的圆盘上均匀采样，再按可见部分压缩
+        let r = random_double().sqrt();
+        let phi = 2.0 * rtweekend::PI_F64 * random_double();
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+
+        //  还原拉伸
+        Vec3::unit_vector(&Vec3::new(
+            self.alpha_x * nh.x(),
+            self.alpha_y * nh.y(),
+            nh.z().max(1e-6),
+        ))
+    }
+
+    /// 按可见法线采样再镜面反射得到 `wi` 的概率密度 D_wo(m) / (4|wo·m|)
+    pub fn reflection_pdf(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        let h = *wo + *wi;
+        if h.length_squared() == 0.0 {
+            return 0.0;
+        }
+        let m = Vec3::unit_vector(&h);
+        let wo_dot_m = Vec3::dot(wo, &m).abs();
+        if wo_dot_m == 0.0 {
+            return 0.0;
+        }
+        self.visible_d(wo, &m) / (4.0 * wo_dot_m)
+    }
+}
+
+/// 局部坐标系中 `w` 关于法线 `m` 的镜面反射方向
+pub fn reflect(w: &Vec3, m: &Vec3) -> Vec3 {
+    2.0 * Vec3::dot(w, m) * *m - *w
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fresnel {
+    Schlick(Color), //  法向入射时的反射率
+    Conductor { eta: Color, k: Color },
+}
+
+//  单一通道的导体菲涅尔反射率，入射介质为真空
+fn fresnel_conductor(cos_theta: f64, eta: f64, k: f64) -> f64 {
+    let cos2 = cos_theta * cos_theta;
+    let sin2 = 1.0 - cos2;
+    let eta2 = eta * eta;
+    let k2 = k * k;
+
+    let t0 = eta2 - k2 - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+    let t2 = 2.0 * cos_theta * a;
+    let rs = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+
+    0.5 * (rp + rs)
+}
+
+impl Fresnel {
+    /// 入射方向与小平面法线夹角余弦为 `cos_theta` 时的反射率
+    pub fn evaluate(&self, cos_theta: f64) -> Color {
+        let cos_theta = cos_theta.clamp(0.0, 1.0);
+        match self {
+            Fresnel::Schlick(f0) => {
+                *f0 + (Color::new(1.0, 1.0, 1.0) - *f0) * (1.0 - cos_theta).powi(5)
+            }
+            Fresnel::Conductor { eta, k } => Color::new(
+                fresnel_conductor(cos_theta, eta.x(), k.x()),
+                fresnel_conductor(cos_theta, eta.y(), k.y()),
+                fresnel_conductor(cos_theta, eta.z(), k.z()),
+            ),
+        }
+    }
+}
+
+/// 电介质的菲涅尔反射率，`eta` 为界面另一侧与入射一侧的折射率之比，`cos_theta` < 0 表示从另一侧入射
+pub fn fresnel_dielectric(cos_theta: f64, eta: f64) -> f64 {
+    let (cos_i, eta) = if cos_theta < 0.0 {
+        (-cos_theta.max(-1.0), 1.0 / eta)
+    } else {
+        (cos_theta.min(1.0), eta)
+    };
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0; //  全反射
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let r_parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    0.5 * (r_parallel * r_parallel + r_perpendicular * r_perpendicular)
+}
+
+/// 局部坐标系中 `w` 经法线为 `m` 的界面折射后的方向，`eta` 含义同上；全反射时返回 None
+pub fn refract(w: &Vec3, m: &Vec3, eta: f64) -> Option<Vec3> {
+    let cos_i = Vec3::dot(w, m);
+    let sin2_t = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(-*w / eta + (cos_i / eta - cos_t) * *m)
+}
+
+/// 粗糙电介质的 f·|cosθi| 与按 `DielectricMicrofacetPdf` 采样到 `wi` 的概率密度。
+/// `wo` 须在上半球，`eta` 为下半球与上半球的折射率之比。
+/// 透射项除以 η 而不是 η²，使 BSDF 对称，双向方法中从光源一侧求值也得到相同的结果；
+/// 与 `Dielectric` 一样不计辐亮度穿过界面时的压缩，光线进出同一物体后这一因子相互抵消
+pub fn rough_dielectric(distribution: &TrowbridgeReitz, wo: &Vec3, wi: &Vec3, eta: f64) -> (f64, f64) {
+    if wo.z() <= 0.0 || wi.z() == 0.0 {
+        return (0.0, 0.0);
+    }
+    let reflect = wi.z() > 0.0;
+    let etap = if reflect { 1.0 } else { eta };
+    let h = *wi * etap + *wo;
+    if h.length_squared() == 0.0 {
+        return (0.0, 0.0);
+    }
+    let mut m = Vec3::unit_vector(&h);
+    if m.z() < 0.0 {
+        m = -m;
+    }
+
+    //  小平面必须从 wo 与 wi 两侧都看得到正确的一面
+    let wo_dot_m = Vec3::dot(wo, &m);
+    let wi_dot_m = Vec3::dot(wi, &m);
+    if wo_dot_m <= 0.0 || wi_dot_m * wi.z() <= 0.0 {
+        return (0.0, 0.0);
+    }
+
+    let d = distribution.d(&m);
+    let g = distribution.g(wo, wi);
+    let f = fresnel_dielectric(wo_dot_m, eta);
+    let visible = distribution.visible_d(wo, &m);
+    if reflect {
+        let value = d * g * f / (4.0 * wo.z());
+        let pdf = visible / (4.0 * wo_dot_m) * f;
+        (value, pdf)
+    } else {
+        let denom = (wi_dot_m + wo_dot_m / etap).powi(2);
+        let value = d * g * (1.0 - f) * (wi_dot_m * wo_dot_m).abs() / (denom * wo.z() * etap);
+        let pdf = visible * wi_dot_m.abs() / denom * (1.0 - f);
+        (value, pdf)
+    }
+}
+
+/// 微表面反射的方向分布：按可见法线采样后镜面反射
+pub struct MicrofacetPdf {
+    uvw: Onb,
+    wo: Vec3, //  局部坐标系中指向表面外的出射方向
+    distribution: TrowbridgeReitz,
+}
+
+impl MicrofacetPdf {
+    /// `wo` 为世界坐标中从表面指向观察者的方向
+    pub fn new(normal: Vec3, wo: Vec3, distribution: TrowbridgeReitz) -> Self {
+        Self::with_frame(Onb::new(normal), wo, distribution)
+    }
+
+    /// 在给定的局部坐标系 `uvw` 中采样，各向异性分布的 x、y 轴与它的切线、副切线对齐
+    pub fn with_frame(uvw: Onb, wo: Vec3, distribution: TrowbridgeReitz) -> Self {
+        let wo = Vec3::unit_vector(&uvw.to_local(&wo));
+        Self {
+            uvw,
+            wo,
+            distribution,
+        }
+    }
+}
+
+impl Pdf for MicrofacetPdf {
+    fn value(&self, direction: &Vec3) -> f64 {
+        let wi = Vec3::unit_vector(&self.uvw.to_local(direction));
+        self.distribution.reflection_pdf(&self.wo, &wi)
+    }
+
+    fn generate(&self) -> Vec3 {
+        let m = self.distribution.sample_visible_normal(&self.wo);
+        self.uvw.transform(&reflect(&self.wo, &m))
+    }
+}
+
+/// 粗糙电介质的方向分布：按可见法线采样，再依菲涅尔反射率随机选择反射或折射
+pub struct DielectricMicrofacetPdf {
+    uvw: Onb,
+    wo: Vec3,
+    eta: f64,
+    distribution: TrowbridgeReitz,
+}
+
+impl DielectricMicrofacetPdf {
+    /// `normal` 与 `wo` 在同一侧，`eta` 为法线背面与正面的折射率之比
+    pub fn new(normal: Vec3, wo: Vec3, eta: f64, distribution: TrowbridgeReitz) -> Self {
+        let uvw = Onb::new(normal);
+        let wo = Vec3::unit_vector(&uvw.to_local(&wo));
+        Self {
+            uvw,
+            wo,
+            eta,
+            distribution,
+        }
+    }
+}
+
+impl Pdf for DielectricMicrofacetPdf {
+    fn value(&self, direction: &Vec3) -> f64 {
+        let wi = Vec3::unit_vector(&self.uvw.to_local(direction));
+        rough_dielectric(&self.distribution, &self.wo, &wi, self.eta).1
+    }
+
+    fn generate(&self) -> Vec3 {
+        let m = self.distribution.sample_visible_normal(&self.wo);
+        let f = fresnel_dielectric(Vec3::dot(&self.wo, &m), self.eta);
+        let wi = if random_double() < f {
+            reflect(&self.wo, &m)
+        } else {
+            refract(&self.wo, &m, self.eta).unwrap_or_else(|| reflect(&self.wo, &m))
+        };
+        self.uvw.transform(&wi)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conductor_fresnel_at_normal_and_grazing_incidence() {
+        let (eta, k) = (Color::new(0.143, 0.374, 1.442), Color::new(3.983, 2.386, 1.603));
+        let fresnel = Fresnel::Conductor { eta, k };
+
+        //  法向入射：R = ((η - 1)² + k²) / ((η + 1)² + k²)
+        let normal = fresnel.evaluate(1.0);
+        for c in 0..3 {
+            let expected = ((eta[c] - 1.0).powi(2) + k[c] * k[c]) / ((eta[c] + 1.0).powi(2) + k[c] * k[c]);
+            assert!((normal[c] - expected).abs() < 1e-9);
+        }
+
+        let grazing = fresnel.evaluate(0.0);
+        for c in 0..3 {
+            assert!((grazing[c] - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn schlick_fresnel_at_normal_and_grazing_incidence() {
+        let f0 = Color::new(0.9, 0.6, 0.2);
+        let fresnel = Fresnel::Schlick(f0);
+        let normal = fresnel.evaluate(1.0);
+        let grazing = fresnel.evaluate(0.0);
+        for c in 0..3 {
+            assert!((normal[c] - f0[c]).abs() < 1e-12);
+            assert!((grazing[c] - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn dielectric_fresnel_at_normal_and_grazing_incidence() {
+        let eta: f64 = 1.5;
+        let normal = ((eta - 1.0) / (eta + 1.0)).powi(2);
+        assert!((fresnel_dielectric(1.0, eta) - normal).abs() < 1e-12);
+        assert!((fresnel_dielectric(-1.0, eta) - normal).abs() < 1e-12);
+        assert!((fresnel_dielectric(0.0, eta) - 1.0).abs() < 1e-12);
+
+        //  从内部射出时超过临界角全反射，临界角以内与外部入射的折射方向互为逆过程，反射率相同
+        let critical = (1.0 / eta).asin();
+        assert_eq!(fresnel_dielectric(-(critical + 0.01).cos(), eta), 1.0);
+        let cos_outside: f64 = 0.6;
+        let sin_inside = (1.0 - cos_outside * cos_outside).sqrt() / eta;
+        let cos_inside = (1.0 - sin_inside * sin_inside).sqrt();
+        let outside = fresnel_dielectric(cos_outside, eta);
+        assert!((fresnel_dielectric(-cos_inside, eta) - outside).abs() < 1e-12);
+    }
+
+    #[test]
+    fn rough_dielectric_pdf_matches_sampling() {
+        //  按 DielectricMicrofacetPdf 采样的方向落在上、下半球的比例与 pdf 在两半球上的积分一致
+        let distribution = TrowbridgeReitz::from_roughness(0.5, 0.5);
+        let wo = Vec3::unit_vector(&Vec3::new(0.4, 0.0, 1.0));
+        let (eta, n) = (1.5, 200_000);
+        let mut reflected = 0;
+        for _ in 0..n {
+            let m = distribution.sample_visible_normal(&wo);
+            if random_double() < fresnel_dielectric(Vec3::dot(&wo, &m), eta) {
+                reflected += 1;
+            }
+        }
+
+        let mut integral = [0.0, 0.0];
+        let (steps_theta, steps_phi) = (400, 400);
+        for i in 0..steps_theta {
+            let cos_theta = -1.0 + 2.0 * (i as f64 + 0.5) / steps_theta as f64;
+            let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+            for j in 0..steps_phi {
+                let phi = 2.0 * rtweekend::PI_F64 * (j as f64 + 0.5) / steps_phi as f64;
+                let wi = Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+                let pdf = rough_dielectric(&distribution, &wo, &wi, eta).1;
+                let d_omega = 4.0 * rtweekend::PI_F64 / (steps_theta * steps_phi) as f64;
+                integral[usize::from(cos_theta < 0.0)] += pdf * d_omega;
+            }
+        }
+        let fraction = reflected as f64 / n as f64;
+        assert!((integral[0] - fraction).abs() < 0.01, "{} != {}", integral[0], fraction);
+        assert!((integral[0] + integral[1] - 1.0).abs() < 0.02);
+    }
+}
@@ -0,0 +1,294 @@
+//! # `mlt.rs` 模块说明
+//!
+//! 主样本空间 Metropolis 光传输（Kelemen et al. 2002, primary sample space MLT）。
+//!
+//! 路径追踪器用到的所有随机数组成一个 [0,1)^n 中的向量（主样本），
+//! 在这个空间里做 Metropolis–Hastings 随机游走，使路径被访问的频率正比于它的亮度：
+//! - 前两个样本决定图像上的位置，其余样本交给 `Camera::path_color` 消耗
+//! - 变异分为大步（所有样本重新均匀采样）与小步（每个样本加上正态扰动并回绕到 [0,1)）
+//! - 渲染前先用 `bootstrap` 条独立路径估计全图平均亮度 b，并按亮度选出各条马尔可夫链的起点
+//!
+//! 随机数通过 `rtweekend::set_sample_source` 接管，因此对路径追踪器本身不需要任何修改。
+
+use crate::camera::Camera;
+use crate::hittable::Hittable;
+use crate::rtweekend::{self, SampleSource, random_double};
+use crate::vec3::Color;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+const ONE_MINUS_EPSILON: f64 = 1.0 - f64::EPSILON / 2.0;
+
+/// Metropolis 光传输的参数
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MltOptions {
+    pub bootstrap: usize,            //  估计归一化常数 b 时使用的路径数
+    pub chains: usize,               //  并行的马尔可夫链数量
+    pub large_step_probability: f64, //  每次变异为大步的概率
+    pub sigma: f64,                  //  小步变异的正态扰动标准差
+}
+
+impl Default for MltOptions {
+    fn default() -> Self {
+        Self {
+            bootstrap: 100_000,
+            chains: 64,
+            large_step_probability: 0.3,
+            sigma: 0.01,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct PrimarySample {
+    value: f64,
+    last_modification_iteration: u64,
+    value_backup: f64,
+    modify_backup: u64,
+}
+
+impl PrimarySample {
+    fn backup(&mut self) {
+        self.value_backup = self.value;
+        self.modify_backup = self.last_modification_iteration;
+    }
+
+    fn restore(&mut self) {
+        self.value = self.value_backup;
+        self.last_modification_iteration = self.modify_backup;
+    }
+}
+
+//  懒惰地生成主样本：只有被用到时才把自上次修改以来错过的变异一次性补上
+struct MltSampler {
+    rng: StdRng,
+    samples: Vec<PrimarySample>,
+    index: usize,
+    current_iteration: u64,
+    large_step: bool,
+    last_large_step_iteration: u64,
+    large_step_probability: f64,
+    sigma: f64,
+}
+
+impl MltSampler {
+    fn new(seed: u64, options: &MltOptions) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            samples: Vec::new(),
+            index: 0,
+            current_iteration: 0,
+            large_step: true,
+            last_large_step_iteration: 0,
+            large_step_probability: options.large_step_probability,
+            sigma: options.sigma,
+        }
+    }
+
+    fn start_iteration(&mut self) {
+        self.current_iteration += 1;
+        self.large_step = self.rng.r#gen::<f64>() < self.large_step_probability;
+        self.index = 0;
+    }
+
+    fn accept(&mut self) {
+        if self.large_step {
+            self.last_large_step_iteration = self.current_iteration;
+        }
+    }
+
+    fn reject(&mut self) {
+        for sample in self.samples.iter_mut() {
+            if sample.last_modification_iteration == self.current_iteration {
+                sample.restore();
+            }
+        }
+        self.current_iteration -= 1;
+    }
+
+    fn ensure_ready(&mut self, index: usize) {
+        //  第一次用到的样本视为在上一次大步时均匀生成，否则小步只会在 0 附近扰动
+        while self.samples.len() <= index {
+            self.samples.push(PrimarySample {
+                value: self.rng.r#gen::<f64>(),
+                last_modification_iteration: self.last_large_step_iteration,
+                ..Default::default()
+            });
+        }
+        let sample = &mut self.samples[index];
+
+        //  上一次大步之后没有被用到的样本，先补上那次大步
+        if sample.last_modification_iteration < self.last_large_step_iteration {
+            sample.value = self.rng.r#gen::<f64>();
+            sample.last_modification_iteration = self.last_large_step_iteration;
+        }
+
+        sample.backup();
+        if self.large_step {
+            sample.value = self.rng.r#gen::<f64>();
+        } else {
+            //  错过的 n 次小步合并为一次标准差为 σ√n 的扰动
+            let n_small = (self.current_iteration - sample.last_modification_iteration) as f64;
+            let u1 = 1.0 - self.rng.r#gen::<f64>();
+            let u2 = self.rng.r#gen::<f64>();
+            let normal = (-2.0 * u1.ln()).sqrt() * (2.0 * rtweekend::PI_F64 * u2).cos();
+            sample.value += normal * self.sigma * n_small.sqrt();
+            sample.value = (sample.value - sample.value.floor()).min(ONE_MINUS_EPSILON);
+        }
+        sample.last_modification_iteration = self.current_iteration;
+    }
+}
+
+impl SampleSource for MltSampler {
+    fn next_sample(&mut self) -> f64 {
+        let index = self.index;
+        self.index += 1;
+        self.ensure_ready(index);
+        self.samples[index].value
+    }
+}
+
+//  一条马尔可夫链的状态：路径落在的像素、颜色与标量贡献
+#[derive(Clone, Copy)]
+struct PathSample {
+    pixel: usize,
+    color: Color,
+    y: f64,
+}
+
+fn luminance(c: &Color) -> f64 {
+    0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
+}
+
+//  用当前线程的随机数来源生成一条完整路径
+fn evaluate(
+    cam: &Camera,
+    world: &dyn Hittable,
+    lights: &Arc<dyn Hittable + Send + Sync>,
+) -> PathSample {
+    let width = cam.image_width;
+    let height = cam.image_height();
+    let x = random_double() * width as f64;
+    let y = random_double() * height as f64;
+    let pixel = (y as usize).min(height - 1) * width + (x as usize).min(width - 1);
+
+    let r = cam.get_ray_raster(x, y);
+    let color = cam.path_color(&r, world, lights.clone());
+    let y = luminance(&color);
+    if y.is_finite() && y > 0.0 {
+        PathSample { pixel, color, y }
+    } else {
+        PathSample {
+            pixel,
+            color: Color::new(0.0, 0.0, 0.0),
+            y: 0.0,
+        }
+    }
+}
+
+//  在给定的采样器下生成一条路径，结束后恢复默认的随机数来源
+fn evaluate_with(
+    sampler: &Rc<RefCell<MltSampler>>,
+    cam: &Camera,
+    world: &dyn Hittable,
+    lights: &Arc<dyn Hittable + Send + Sync>,
+) -> PathSample {
+    rtweekend::set_sample_source(Some(sampler.clone()));
+    let sample = evaluate(cam, world, lights);
+    rtweekend::set_sample_source(None);
+    sample
+}
+
+/// 运行全部马尔可夫链，返回按行排列的像素颜色。总变异次数为 `sample_per_pixel × 像素数`
+pub fn render(
+    cam: &Camera,
+    world: &dyn Hittable,
+    lights: &Arc<dyn Hittable + Send + Sync>,
+    options: &MltOptions,
+) -> Vec<Color> {
+    let pixel_count = cam.image_width * cam.image_height();
+    let bootstrap = options.bootstrap.max(1);
+    let chains = options.chains.max(1);
+
+    //  bootstrap：独立的大步路径，既估计 b 也作为各条链的候选起点
+    let weights: Vec<f64> = (0..bootstrap)
+        .into_par_iter()
+        .map(|i| {
+            let sampler = Rc::new(RefCell::new(MltSampler::new(i as u64, options)));
+            evaluate_with(&sampler, cam, world, lights).y
+        })
+        .collect();
+    let b = weights.iter().sum::<f64>() / bootstrap as f64;
+    if b <= 0.0 {
+        return vec![Color::new(0.0, 0.0, 0.0); pixel_count];
+    }
+
+    let mut cdf = Vec::with_capacity(bootstrap);
+    let mut total = 0.0;
+    for w in &weights {
+        total += w;
+        cdf.push(total);
+    }
+
+    let total_mutations = cam.sample_per_pixel.max(1) * pixel_count;
+    let framebuffer = Mutex::new(vec![Color::new(0.0, 0.0, 0.0); pixel_count]);
+
+    (0..chains).into_par_iter().for_each(|chain| {
+        let mutations = total_mutations / chains + usize::from(chain < total_mutations % chains);
+        if mutations == 0 {
+            return;
+        }
+
+        //  按亮度选一条 bootstrap 路径，用相同的种子重现它作为起点
+        let u = random_double() * total;
+        let start = cdf.partition_point(|&c| c <= u).min(bootstrap - 1);
+        let sampler = Rc::new(RefCell::new(MltSampler::new(start as u64, options)));
+        let mut current = evaluate_with(&sampler, cam, world, lights);
+        sampler.borrow_mut().rng = StdRng::seed_from_u64((bootstrap + chain) as u64);
+
+        let mut local = vec![Color::new(0.0, 0.0, 0.0); pixel_count];
+        for _ in 0..mutations {
+            sampler.borrow_mut().start_iteration();
+            let proposed = evaluate_with(&sampler, cam, world, lights);
+
+            let accept = if current.y > 0.0 {
+                (proposed.y / current.y).min(1.0)
+            } else {
+                1.0
+            };
+
+            //  期望值形式的累加：提议与当前状态按接受概率分摊贡献
+            if accept > 0.0 {
+                local[proposed.pixel] += proposed.color * (accept / proposed.y);
+            }
+            if current.y > 0.0 {
+                local[current.pixel] += current.color * ((1.0 - accept) / current.y);
+            }
+
+            if random_double() < accept {
+                current = proposed;
+                sampler.borrow_mut().accept();
+            } else {
+                sampler.borrow_mut().reject();
+            }
+        }
+
+        let mut fb = framebuffer.lock().unwrap();
+        for (pixel, color) in fb.iter_mut().zip(local) {
+            *pixel += color;
+        }
+    });
+
+    let scale = b * pixel_count as f64 / total_mutations as f64;
+    framebuffer
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|color| scale * color)
+        .collect()
+}
@@ -0,0 +1,204 @@
+//! # `normal_map.rs` 模块说明
+//!
+//! 给任意材质加上法线贴图或凹凸贴图：只改变着色用的法线，不改变物体形状与交点。
+//!
+//! - `NormalMap::new`：切线空间法线贴图，颜色 (r, g, b) 映射为 (2r - 1, 2g - 1, 2b - 1)，
+//!   x 沿 ∂p/∂u，y 沿 n × ∂p/∂u（贴图向上，OpenGL 约定），z 沿外法线；通常使用 `ImageTexture`
+//! - `NormalMap::from_bump`：高度纹理的第一个分量乘以 `scale` 作为沿外法线的位移，
+//!   按 u、v 方向的有限差分求出位移后的切线，叉乘得到新的法线。纹理在偏移后的 (u, v, p) 处求值，
+//!   因此 `ImageTexture` 与 `NoiseTexture` 这样的实体纹理都可以使用
+//! - 被包装的材质看到的是法线换成着色法线的 `HitRecord`；入射或出射方向相对几何法线与着色法线
+//!   位于不同侧时视为被遮挡，避免光线穿过表面或从背面漏进来，也使两个方向的传输保持对称
+//! - 切线退化的地方（球的两极、参与介质）保持几何法线
+//! - 着色法线通过 `Material::shading_normal` 公开，从光源出发的路径据此做伴随修正
+//! - 同一个交点上的 `scatter`、`scattering_pdf`、`scattering_value` 与 `shading_normal` 共用一次计算的结果（`HitRecord::shading`）
+
+use std::sync::Arc;
+
+use crate::hittable::HitRecord;
+use crate::material::{Material, ScatterRecord};
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::texture::Texture;
+use crate::vec3::{Color, Point3, Vec3};
+
+//  凹凸贴图有限差分的参数步长
+const BUMP_DELTA: f64 = 0.0005;
+
+#[derive(Debug)]
+enum Perturbation {
+    Normal(Arc<dyn Texture>),
+    Bump { height: Arc<dyn Texture>, scale: f64 },
+}
+
+#[derive(Debug)]
+pub struct NormalMap {
+    material: Arc<dyn Material>,
+    perturbation: Perturbation,
+}
+
+impl NormalMap {
+    /// 以切线空间法线贴图 `normals` 扰动 `material` 的着色法线
+    pub fn new(material: Arc<dyn Material>, normals: Arc<dyn Texture>) -> Self {
+        Self {
+            material,
+            perturbation: Perturbation::Normal(normals),
+        }
+    }
+
+    /// 以高度纹理 `height` 扰动 `material` 的着色法线，位移为纹理值乘以 `scale`（与场景同一长度单位）
+    pub fn from_bump(material: Arc<dyn Material>, height: Arc<dyn Texture>, scale: f64) -> Self {
+        Self {
+            material,
+            perturbation: Perturbation::Bump { height, scale },
+        }
+    }
+
+    //  与外法线 `outward` 同侧的着色法线
+    fn perturbed_normal(&self, rec: &HitRecord, outward: Vec3) -> Vec3 {
+        let n = match &self.perturbation {
+            Perturbation::Normal(normals) => {
+                let c = normals.value(rec.u, rec.v, &rec.p);
+                let local = Vec3::new(2.0 * c.x() - 1.0, 2.0 * c.y() - 1.0, 2.0 * c.z() - 1.0);
+                Onb::from_normal_tangent(outward, rec.dpdu).transform(&local)
+            }
+            Perturbation::Bump { height, scale } => {
+                let displacement = |du: f64, dv: f64| {
+                    let p = rec.p + du * rec.dpdu + dv * rec.dpdv;
+                    scale * height.value(rec.u + du, rec.v + dv, &p).x()
+                };
+                let d = displacement(0.0, 0.0);
+                let dpdu = rec.dpdu + (displacement(BUMP_DELTA, 0.0) - d) / BUMP_DELTA * outward;
+                let dpdv = rec.dpdv + (displacement(0.0, BUMP_DELTA) - d) / BUMP_DELTA * outward;
+                Vec3::cross(&dpdu, &dpdv)
+            }
+        };
+
+        let degenerate = Vec3::cross(&rec.dpdu, &rec.dpdv).length_squared() <= 0.0;
+        if degenerate || n.length_squared() <= 0.0 {
+            return outward;
+        }
+        let n = Vec3::unit_vector(&n);
+        if Vec3::dot(&n, &outward) < 0.0 { -n } else { n }
+    }
+
+    //  法线换成着色法线后的交点；着色法线在每个交点上只计算一次，缓存在 `rec.shading` 中
+    fn shading_record<'a>(&self, rec: &HitRecord<'a>) -> HitRecord<'a> {
+        let outward = if rec.front_face { rec.normal } else { -rec.normal };
+        let n = rec
+            .shading
+            .get_or_insert_with(self as *const Self as usize, || self.perturbed_normal(rec, outward));
+        let mut shading = rec.clone();
+        shading.normal = if rec.front_face { n } else { -n };
+        shading
+    }
+
+    //  方向相对几何法线与着色法线是否在同一侧
+    fn consistent(rec: &HitRecord, shading: &HitRecord, direction: &Vec3) -> bool {
+        Vec3::dot(direction, &rec.normal) * Vec3::dot(direction, &shading.normal) > 0.0
+    }
+}
+
+impl Material for NormalMap {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let shading = self.shading_record(rec);
+        if !Self::consistent(rec, &shading, &-*r_in.direction()) {
+            return None;
+        }
+        let srec = self.material.scatter(r_in, &shading)?;
+        let skip_inconsistent = srec
+            .skip_pdf_ray
+            .as_ref()
+            .is_some_and(|ray| !Self::consistent(rec, &shading, ray.direction()));
+        if skip_inconsistent {
+            return None;
+        }
+        Some(srec)
+    }
+
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord, u: f64, v: f64, p: &Point3) -> Color {
+        self.material.emitted(r_in, rec, u, v, p)
+    }
+
+    fn pdf_probability(&self, r_in: &Ray, rec: &HitRecord) -> f64 {
+        self.material.pdf_probability(r_in, &self.shading_record(rec))
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.material.scattering_pdf(r_in, &self.shading_record(rec), scattered)
+    }
+
+    fn scattering_value(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        let shading = self.shading_record(rec);
+        if !Self::consistent(rec, &shading, &-*r_in.direction())
+            || !Self::consistent(rec, &shading, scattered.direction())
+        {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        self.material.scattering_value(r_in, &shading, scattered)
+    }
+
+    fn shading_normal(&self, rec: &HitRecord) -> Vec3 {
+        self.shading_record(rec).normal
+    }
+
+    fn is_volumetric(&self) -> bool {
+        self.material.is_volumetric()
+    }
+
+    fn is_two_sided_emitter(&self) -> bool {
+        self.material.is_two_sided_emitter()
+    }
+
+    fn is_holdout(&self) -> bool {
+        self.material.is_holdout()
+    }
+
+    fn is_shadow_catcher(&self) -> bool {
+        self.material.is_shadow_catcher()
+    }
+
+    fn transmittance(&self, r_in: &Ray, rec: &HitRecord) -> Color {
+        self.material.transmittance(r_in, rec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittable;
+    use crate::interval::Interval;
+    use crate::material::Lambertian;
+    use crate::quad::Quad;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    //  记录求值次数的法线贴图，法线略微倾斜
+    #[derive(Debug, Default)]
+    struct CountingNormals(AtomicUsize);
+
+    impl Texture for CountingNormals {
+        fn value(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Color::new(0.6, 0.5, 0.9)
+        }
+    }
+
+    #[test]
+    fn shading_normal_is_computed_once_per_hit() {
+        let normals = Arc::new(CountingNormals::default());
+        let material = Arc::new(NormalMap::new(Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))), normals.clone()));
+        let quad = Quad::new(Point3::new(-1.0, -1.0, 0.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 2.0, 0.0), material);
+        let r = Ray::new(Point3::new(0.2, 0.1, 1.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let rec = quad.hit(&r, &Interval::new(0.001, f64::INFINITY)).unwrap();
+
+        let ns = rec.mat.shading_normal(&rec);
+        assert!(Vec3::dot(&ns, &rec.normal) > 0.0 && (ns - rec.normal).length() > 1e-3);
+        let scattered = Ray::new(rec.p, Vec3::new(0.1, 0.0, 1.0), 0.0);
+        rec.mat.pdf_probability(&r, &rec);
+        rec.mat.scatter(&r, &rec);
+        rec.mat.scattering_pdf(&r, &rec, &scattered);
+        rec.mat.scattering_value(&r, &rec, &scattered);
+        assert_eq!(rec.mat.shading_normal(&rec), ns);
+        assert_eq!(normals.0.load(Ordering::Relaxed), 1);
+    }
+}
@@ -20,6 +20,21 @@ impl Onb {
         }
     }
 
+    //  着色坐标系：w 为法线 n，u 为切线 t 去掉法向分量后的方向，v = w × u。
+    //  t 为零或与 n 平行时退化为 `Onb::new`
+    pub fn from_normal_tangent(n: Vec3, t: Vec3) -> Self {
+        let w = Vec3::unit_vector(&n);
+        let tangent = t - Vec3::dot(&t, &w) * w;
+        if tangent.length_squared() <= 1e-12 * t.length_squared() {
+            return Self::new(n);
+        }
+        let u = Vec3::unit_vector(&tangent);
+        let v = Vec3::cross(&w, &u);
+        Self {
+            axis: [u, v, w],
+        }
+    }
+
     pub fn u(&self) -> &Vec3 {
         &self.axis[0]
     }
@@ -35,4 +50,13 @@ impl Onb {
     pub fn transform(&self, v: &Vec3) -> Vec3 {
         v[0] * self.axis[0] + v[1] * self.axis[1] + v[2] * self.axis[2]
     }
+
+    //  transform 的逆变换：世界坐标下的向量在 u、v、w 上的分量
+    pub fn to_local(&self, v: &Vec3) -> Vec3 {
+        Vec3::new(
+            Vec3::dot(v, &self.axis[0]),
+            Vec3::dot(v, &self.axis[1]),
+            Vec3::dot(v, &self.axis[2]),
+        )
+    }
 }
\ No newline at end of file
@@ -96,4 +96,35 @@ impl Pdf for MixturePdf {
             self.p[1].generate()
         }
     }
-}
\ No newline at end of file
+}
+/// 按权重混合任意多个概率密度，权重不必归一化；为 0 的项会被忽略，但至少要有一项大于 0
+pub struct WeightedMixturePdf {
+    p: Vec<(f64, Arc<dyn Pdf + Send + Sync>)>,
+}
+
+impl WeightedMixturePdf {
+    pub fn new(p: Vec<(f64, Arc<dyn Pdf + Send + Sync>)>) -> Self {
+        let p: Vec<_> = p.into_iter().filter(|(weight, _)| *weight > 0.0).collect();
+        let total: f64 = p.iter().map(|(weight, _)| weight).sum();
+        Self {
+            p: p.into_iter().map(|(weight, pdf)| (weight / total, pdf)).collect(),
+        }
+    }
+}
+
+impl Pdf for WeightedMixturePdf {
+    fn value(&self, direction: &Vec3) -> f64 {
+        self.p.iter().map(|(weight, pdf)| weight * pdf.value(direction)).sum()
+    }
+
+    fn generate(&self) -> Vec3 {
+        let mut xi = rtweekend::random_double();
+        for (weight, pdf) in &self.p[..self.p.len() - 1] {
+            if xi < *weight {
+                return pdf.generate();
+            }
+            xi -= weight;
+        }
+        self.p[self.p.len() - 1].1.generate()
+    }
+}
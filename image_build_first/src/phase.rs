@@ -0,0 +1,247 @@
+//! # `phase.rs` 模块说明
+//!
+//! 参与介质的相函数。相函数只依赖散射角 θ 的余弦，θ 是入射射线的传播方向与散射方向之间的夹角，
+//! 因此 cosθ > 0 表示向前散射。所有相函数都在单位球面上归一化，并且可以精确地重要性采样：
+//! - `HenyeyGreenstein`：单参数 g ∈ (-1, 1) 控制前向/后向散射，g = 0 时退化为各向同性
+//! - `DoubleHenyeyGreenstein`：两个 HG 波瓣的加权和，可以同时有前向峰与后向峰
+//! - `Rayleigh`：远小于波长的粒子（空气分子）的散射
+//! - `Mie`：较大粒子（雾、云中的水滴）的 Cornette–Shanks 近似
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use crate::onb::Onb;
+use crate::pdf::Pdf;
+use crate::rtweekend::{self, random_double};
+use crate::vec3::Vec3;
+
+pub trait PhaseFunction: Send + Sync + Debug {
+    /// 散射角余弦为 `cos_theta` 时的相函数值（立体角测度下的概率密度）
+    fn evaluate(&self, cos_theta: f64) -> f64;
+
+    /// 按相函数采样一个散射角余弦
+    fn sample_cos_theta(&self) -> f64;
+}
+
+//  HG 相函数，供单波瓣、双波瓣与 Mie 共用
+fn henyey_greenstein(cos_theta: f64, g: f64) -> f64 {
+    let denom = 1.0 + g * g - 2.0 * g * cos_theta;
+    (1.0 - g * g) / (4.0 * rtweekend::PI_F64 * denom * denom.sqrt())
+}
+
+fn sample_henyey_greenstein(g: f64) -> f64 {
+    let xi = random_double();
+    if g.abs() < 1e-3 {
+        return 1.0 - 2.0 * xi;
+    }
+    let sqr_term = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+    ((1.0 + g * g - sqr_term * sqr_term) / (2.0 * g)).clamp(-1.0, 1.0)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct HenyeyGreenstein {
+    g: f64,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(g: f64) -> Self {
+        Self {
+            g: g.clamp(-0.999, 0.999),
+        }
+    }
+}
+
+impl PhaseFunction for HenyeyGreenstein {
+    fn evaluate(&self, cos_theta: f64) -> f64 {
+        henyey_greenstein(cos_theta, self.g)
+    }
+
+    fn sample_cos_theta(&self) -> f64 {
+        sample_henyey_greenstein(self.g)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DoubleHenyeyGreenstein {
+    g1: f64,
+    g2: f64,
+    weight: f64, //  第一个波瓣所占的权重
+}
+
+impl DoubleHenyeyGreenstein {
+    pub fn new(g1: f64, g2: f64, weight: f64) -> Self {
+        Self {
+            g1: g1.clamp(-0.999, 0.999),
+            g2: g2.clamp(-0.999, 0.999),
+            weight: weight.clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl PhaseFunction for DoubleHenyeyGreenstein {
+    fn evaluate(&self, cos_theta: f64) -> f64 {
+        self.weight * henyey_greenstein(cos_theta, self.g1)
+            + (1.0 - self.weight) * henyey_greenstein(cos_theta, self.g2)
+    }
+
+    fn sample_cos_theta(&self) -> f64 {
+        if random_double() < self.weight {
+            sample_henyey_greenstein(self.g1)
+        } else {
+            sample_henyey_greenstein(self.g2)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Rayleigh;
+
+impl Rayleigh {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl PhaseFunction for Rayleigh {
+    fn evaluate(&self, cos_theta: f64) -> f64 {
+        3.0 / (16.0 * rtweekend::PI_F64) * (1.0 + cos_theta * cos_theta)
+    }
+
+    fn sample_cos_theta(&self) -> f64 {
+        //  反解累积分布 (μ³ + 3μ + 4) / 8 = ξ，用 Cardano 公式求三次方程的实根
+        let q = 4.0 * random_double() - 2.0;
+        let root = (q * q + 1.0).sqrt();
+        ((q + root).cbrt() + (q - root).cbrt()).clamp(-1.0, 1.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Mie {
+    g: f64,
+}
+
+impl Mie {
+    pub fn new(g: f64) -> Self {
+        Self {
+            g: g.clamp(-0.999, 0.999),
+        }
+    }
+}
+
+impl PhaseFunction for Mie {
+    //  Cornette–Shanks：HG 乘以 3(1 + cos²θ) / (2(2 + g²))
+    fn evaluate(&self, cos_theta: f64) -> f64 {
+        let g2 = self.g * self.g;
+        henyey_greenstein(cos_theta, self.g) * 3.0 * (1.0 + cos_theta * cos_theta)
+            / (2.0 * (2.0 + g2))
+    }
+
+    //  以 HG 为提议分布做拒绝采样，接受概率为 (1 + cos²θ) / 2
+    fn sample_cos_theta(&self) -> f64 {
+        loop {
+            let cos_theta = sample_henyey_greenstein(self.g);
+            if 2.0 * random_double() < 1.0 + cos_theta * cos_theta {
+                return cos_theta;
+            }
+        }
+    }
+}
+
+/// 以入射射线的传播方向为轴、按相函数分布的方向概率密度
+pub struct PhasePdf {
+    uvw: Onb,
+    phase: Arc<dyn PhaseFunction>,
+}
+
+impl PhasePdf {
+    pub fn new(direction: Vec3, phase: Arc<dyn PhaseFunction>) -> Self {
+        Self {
+            uvw: Onb::new(direction),
+            phase,
+        }
+    }
+}
+
+impl Pdf for PhasePdf {
+    fn value(&self, direction: &Vec3) -> f64 {
+        let cos_theta = Vec3::dot(&Vec3::unit_vector(direction), self.uvw.w());
+        self.phase.evaluate(cos_theta)
+    }
+
+    fn generate(&self) -> Vec3 {
+        let cos_theta = self.phase.sample_cos_theta();
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * rtweekend::PI_F64 * random_double();
+        self.uvw
+            .transform(&Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phases() -> Vec<Arc<dyn PhaseFunction>> {
+        vec![
+            Arc::new(HenyeyGreenstein::new(0.0)),
+            Arc::new(HenyeyGreenstein::new(0.7)),
+            Arc::new(HenyeyGreenstein::new(-0.4)),
+            Arc::new(DoubleHenyeyGreenstein::new(0.8, -0.3, 0.7)),
+            Arc::new(Rayleigh::new()),
+            Arc::new(Mie::new(0.6)),
+        ]
+    }
+
+    //  相函数在 [lo, hi] 内的 cosθ 上积分，乘以方位角 2π
+    fn integrate(phase: &dyn PhaseFunction, lo: f64, hi: f64) -> f64 {
+        let steps = 2000;
+        let width = (hi - lo) / steps as f64;
+        (0..steps)
+            .map(|i| phase.evaluate(lo + (i as f64 + 0.5) * width) * width)
+            .sum::<f64>()
+            * 2.0
+            * rtweekend::PI_F64
+    }
+
+    #[test]
+    fn phase_functions_are_normalized() {
+        for phase in phases() {
+            let total = integrate(&*phase, -1.0, 1.0);
+            assert!((total - 1.0).abs() < 1e-4, "{:?}: {}", phase, total);
+        }
+    }
+
+    #[test]
+    fn sampling_matches_evaluate() {
+        let (bins, n) = (10, 200_000);
+        for phase in phases() {
+            let mut counts = vec![0usize; bins];
+            for _ in 0..n {
+                let cos_theta = phase.sample_cos_theta();
+                assert!((-1.0..=1.0).contains(&cos_theta));
+                let bin = (((cos_theta + 1.0) / 2.0 * bins as f64) as usize).min(bins - 1);
+                counts[bin] += 1;
+            }
+            for (bin, &count) in counts.iter().enumerate() {
+                let lo = -1.0 + 2.0 * bin as f64 / bins as f64;
+                let expected = integrate(&*phase, lo, lo + 2.0 / bins as f64);
+                let fraction = count as f64 / n as f64;
+                assert!((fraction - expected).abs() < 0.005, "{:?} bin {}: {} != {}", phase, bin, fraction, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn phase_pdf_is_centered_on_the_ray_direction() {
+        let direction = Vec3::new(0.3, -1.0, 0.5);
+        let axis = Vec3::unit_vector(&direction);
+        let phase: Arc<dyn PhaseFunction> = Arc::new(HenyeyGreenstein::new(0.5));
+        let pdf = PhasePdf::new(direction, phase.clone());
+        for _ in 0..100 {
+            let w = pdf.generate();
+            let cos_theta = Vec3::dot(&Vec3::unit_vector(&w), &axis);
+            assert!((pdf.value(&w) - phase.evaluate(cos_theta)).abs() < 1e-9);
+        }
+        assert!((pdf.value(&direction) - phase.evaluate(1.0)).abs() < 1e-9);
+    }
+}
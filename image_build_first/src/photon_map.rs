@@ -0,0 +1,374 @@
+//! # `photon_map.rs` 模块说明
+//!
+//! 光子映射（Jensen 1996）。渲染前从光源发射光子，光子在场景中按材质散射，
+//! 每次落在非镜面表面上时被存入 kd-tree，渲染时在着色点附近收集光子做密度估计。
+//!
+//! 着色点（相机射线穿过镜面后第一次遇到的非镜面表面）的辐亮度被拆成互不重叠的几部分：
+//! - 自发光，以及按光源与 BSDF 混合采样得到的直接光照
+//! - 焦散：光源经过一次或多次镜面反射/折射后到达该点，由焦散光子图估计
+//! - 间接光照：光子至少经过一次非镜面散射后到达该点，由全局光子图估计
+//!
+//! 参与介质中不存储光子，相机射线在介质中按相函数继续追踪。
+//! 只有 `lights` 中能在表面上采样的光源（`Hittable::sample_surface`）会发射光子。
+
+use crate::camera::Camera;
+use crate::hittable::{HitRecord, Hittable};
+use crate::integrator::{adjoint_scattering_value, emitted_toward, sample_emission};
+use crate::interval::Interval;
+use crate::pdf::{HittablePdf, MixturePdf, Pdf};
+use crate::ray::Ray;
+use crate::rtweekend::{self, random_double};
+use crate::vec3::{Color, Point3, Vec3};
+
+use rayon::prelude::*;
+use std::sync::Arc;
+
+//  每个并行任务发射的光子数
+const PHOTON_BATCH: usize = 10_000;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Photon {
+    pub p: Point3,
+    pub direction: Vec3, //  光子到达时的运动方向
+    pub power: Color,
+}
+
+/// 按中位数划分的平衡 kd-tree，节点隐式地存放在数组中：区间 [lo, hi) 的根为中点
+pub struct PhotonMap {
+    photons: Vec<Photon>,
+    axes: Vec<usize>,
+}
+
+impl PhotonMap {
+    pub fn new(mut photons: Vec<Photon>) -> Self {
+        let mut axes = vec![0; photons.len()];
+        Self::build(&mut photons, &mut axes);
+        Self { photons, axes }
+    }
+
+    fn build(photons: &mut [Photon], axes: &mut [usize]) {
+        if photons.is_empty() {
+            return;
+        }
+
+        //  沿包围盒最长的轴划分
+        let mut min = photons[0].p;
+        let mut max = photons[0].p;
+        for photon in photons.iter() {
+            for i in 0..3 {
+                min[i] = min[i].min(photon.p[i]);
+                max[i] = max[i].max(photon.p[i]);
+            }
+        }
+        let extent = max - min;
+        let axis = if extent.x() > extent.y() && extent.x() > extent.z() {
+            0
+        } else if extent.y() > extent.z() {
+            1
+        } else {
+            2
+        };
+
+        let mid = photons.len() / 2;
+        photons.select_nth_unstable_by(mid, |a, b| a.p[axis].total_cmp(&b.p[axis]));
+        axes[mid] = axis;
+
+        let (left, right) = photons.split_at_mut(mid);
+        let (left_axes, right_axes) = axes.split_at_mut(mid);
+        Self::build(left, left_axes);
+        Self::build(&mut right[1..], &mut right_axes[1..]);
+    }
+
+    pub fn len(&self) -> usize {
+        self.photons.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.photons.is_empty()
+    }
+
+    /// 对与 `p` 距离不超过 `radius` 的每个光子调用 `f`
+    pub fn for_each_in_radius<F: FnMut(&Photon)>(&self, p: &Point3, radius: f64, mut f: F) {
+        self.search(0, self.photons.len(), p, radius * radius, &mut f);
+    }
+
+    fn search<F: FnMut(&Photon)>(
+        &self,
+        lo: usize,
+        hi: usize,
+        p: &Point3,
+        radius_squared: f64,
+        f: &mut F,
+    ) {
+        if lo >= hi {
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let photon = &self.photons[mid];
+        if (photon.p - *p).length_squared() <= radius_squared {
+            f(photon);
+        }
+
+        let axis = self.axes[mid];
+        let delta = p[axis] - photon.p[axis];
+        let (near, far) = if delta < 0.0 {
+            ((lo, mid), (mid + 1, hi))
+        } else {
+            ((mid + 1, hi), (lo, mid))
+        };
+        self.search(near.0, near.1, p, radius_squared, f);
+        if delta * delta <= radius_squared {
+            self.search(far.0, far.1, p, radius_squared, f);
+        }
+    }
+}
+
+pub struct PhotonMaps {
+    caustic: PhotonMap,
+    global: PhotonMap,
+    radius: f64,
+}
+
+impl PhotonMaps {
+    /// 从 `lights` 发射 `photon_count` 个光子并建立焦散与全局两张光子图
+    pub fn build(
+        world: &dyn Hittable,
+        lights: &dyn Hittable,
+        photon_count: usize,
+        radius: f64,
+        max_depth: usize,
+    ) -> Self {
+        let batches = photon_count.div_ceil(PHOTON_BATCH);
+        let (caustic, global) = (0..batches)
+            .into_par_iter()
+            .map(|batch| {
+                let count = PHOTON_BATCH.min(photon_count - batch * PHOTON_BATCH);
+                let mut caustic = Vec::new();
+                let mut global = Vec::new();
+                for _ in 0..count {
+                    Self::trace_photon(
+                        world,
+                        lights,
+                        photon_count,
+                        max_depth,
+                        &mut caustic,
+                        &mut global,
+                    );
+                }
+                (caustic, global)
+            })
+            .reduce(
+                || (Vec::new(), Vec::new()),
+                |mut a, mut b| {
+                    a.0.append(&mut b.0);
+                    a.1.append(&mut b.1);
+                    a
+                },
+            );
+
+        let caustic = PhotonMap::new(caustic);
+        let global = PhotonMap::new(global);
+        Self {
+            caustic,
+            global,
+            radius,
+        }
+    }
+
+    fn trace_photon(
+        world: &dyn Hittable,
+        lights: &dyn Hittable,
+        photon_count: usize,
+        max_depth: usize,
+        caustic: &mut Vec<Photon>,
+        global: &mut Vec<Photon>,
+    ) {
+        let time = random_double();
+        let Some((rec, pdf_pos)) = lights.sample_surface(time) else {
+            return;
+        };
+        if pdf_pos <= 0.0 {
+            return;
+        }
+
+        //  按余弦分布发射：power = Le·cosθ / (pdf_pos·pdf_dir·N)
+        let (direction, pdf_dir) = sample_emission(&rec);
+        if pdf_dir <= 0.0 {
+            return;
+        }
+        let cos_light = Vec3::dot(&Vec3::unit_vector(&direction), &rec.normal).abs();
+        let le = emitted_toward(&rec, &(rec.p + direction), time);
+        let mut power = le * cos_light / (pdf_pos * pdf_dir * photon_count as f64);
+        let mut r = Ray::new(rec.p, direction, time);
+
+        let mut bounces = 0;
+        let mut specular_only = true;
+        while bounces < max_depth && power.max_component() > 0.0 {
+            let Some(rec) = world.hit(&r, &Interval::new(0.001, f64::INFINITY)) else {
+                break;
+            };
+            power = power * r.medium_transmittance(rec.t);
+            let Some(srec) = rec.mat.scatter(&r, &rec) else {
+                break;
+            };
+
+            if let Some(pdf) = srec.pdf_ptr.as_ref() {
+                //  直接光照由着色时的光源采样负责，光源发出后第一次落点不存储
+                if !rec.mat.is_volumetric() {
+                    let photon = Photon {
+                        p: rec.p,
+                        direction: Vec3::unit_vector(r.direction()),
+                        power,
+                    };
+                    if !specular_only {
+                        global.push(photon);
+                    } else if bounces > 0 {
+                        caustic.push(photon);
+                    }
+                }
+
+                let direction = pdf.generate();
+                let pdf_value = pdf.value(&direction);
+                if pdf_value <= 0.0 {
+                    break;
+                }
+                let scattered = r.scattered(rec.p, direction);
+                let new_power = power * adjoint_scattering_value(&r, &rec, &scattered) / pdf_value;
+
+                //  俄罗斯轮盘：按吞吐量的变化决定光子是否继续
+                let survive = (new_power.max_component() / power.max_component()).min(1.0);
+                if random_double() >= survive {
+                    break;
+                }
+                power = new_power / survive;
+                specular_only = false;
+                r = scattered;
+            } else if let Some(pdf_ray) = srec.skip_pdf_ray {
+                power = power * srec.attenuation;
+                r = pdf_ray;
+            } else {
+                break;
+            }
+            bounces += 1;
+        }
+    }
+
+    /// 光子映射估计一条相机射线带回的辐亮度
+    pub fn li(
+        &self,
+        cam: &Camera,
+        r: &Ray,
+        world: &dyn Hittable,
+        lights: &Arc<dyn Hittable + Send + Sync>,
+        depth: usize,
+    ) -> Color {
+        if depth == 0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let hit = world.hit(r, &Interval::new(0.001, f64::INFINITY));
+        self.li_from_hit(cam, r, hit.as_ref(), world, lights, depth)
+    }
+
+    /// 与 `li` 相同，`hit` 是射线在 `world` 中已经求出的第一个交点
+    pub fn li_from_hit(
+        &self,
+        cam: &Camera,
+        r: &Ray,
+        hit: Option<&HitRecord>,
+        world: &dyn Hittable,
+        lights: &Arc<dyn Hittable + Send + Sync>,
+        depth: usize,
+    ) -> Color {
+        if depth == 0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        match hit {
+            Some(rec) => r.medium_transmittance(rec.t) * self.shade(cam, r, rec, world, lights, depth),
+            None => r.medium_transmittance(f64::INFINITY) * cam.background_color(r),
+        }
+    }
+
+    //  相机路径上的命中点 `rec` 带回的辐亮度
+    fn shade(
+        &self,
+        cam: &Camera,
+        r: &Ray,
+        rec: &HitRecord,
+        world: &dyn Hittable,
+        lights: &Arc<dyn Hittable + Send + Sync>,
+        depth: usize,
+    ) -> Color {
+        let color_from_emission = rec.mat.emitted(r, rec, rec.u, rec.v, &rec.p);
+        let Some(srec) = rec.mat.scatter(r, rec) else {
+            return color_from_emission;
+        };
+
+        if let Some(pdf) = srec.pdf_ptr {
+            let (scattered, pdf_value) = Self::sample_direction(r, rec, pdf, lights);
+            if rec.mat.is_volumetric() {
+                //  介质中没有光子，按路径追踪继续
+                if pdf_value <= 0.0 {
+                    return color_from_emission;
+                }
+                let sample_color = self.li(cam, &scattered, world, lights, depth - 1);
+                return color_from_emission
+                    + rec.mat.scattering_value(r, rec, &scattered) * sample_color / pdf_value;
+            }
+
+            let direct = if pdf_value > 0.0 {
+                let light_color = match world.hit(&scattered, &Interval::new(0.001, f64::INFINITY)) {
+                    Some(light_rec) => {
+                        scattered.medium_transmittance(light_rec.t)
+                            * light_rec.mat.emitted(&scattered, &light_rec, light_rec.u, light_rec.v, &light_rec.p)
+                    }
+                    None => scattered.medium_transmittance(f64::INFINITY) * cam.background_color(&scattered),
+                };
+                rec.mat.scattering_value(r, rec, &scattered) * light_color / pdf_value
+            } else {
+                Color::new(0.0, 0.0, 0.0)
+            };
+
+            color_from_emission
+                + direct
+                + self.estimate(&self.caustic, r, rec)
+                + self.estimate(&self.global, r, rec)
+        } else if let Some(pdf_ray) = srec.skip_pdf_ray {
+            color_from_emission
+                + srec.attenuation * self.li(cam, &pdf_ray, world, lights, depth - 1)
+        } else {
+            color_from_emission
+        }
+    }
+
+    //  与 `Camera::ray_color` 相同：光源与材质各占一半的混合采样
+    fn sample_direction(
+        r: &Ray,
+        rec: &HitRecord,
+        pdf: Arc<dyn Pdf + Send + Sync>,
+        lights: &Arc<dyn Hittable + Send + Sync>,
+    ) -> (Ray, f64) {
+        let light_ptr = Arc::new(HittablePdf::new(lights.clone(), rec.p));
+        let p = MixturePdf::new(light_ptr, pdf);
+        let scattered = r.scattered(rec.p, p.generate());
+        let pdf_value = p.value(scattered.direction());
+        (scattered, pdf_value)
+    }
+
+    //  半径内的光子功率乘以 BSDF，除以圆盘面积 πr²
+    fn estimate(&self, map: &PhotonMap, r: &Ray, rec: &HitRecord) -> Color {
+        if map.is_empty() {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        let mut sum = Color::new(0.0, 0.0, 0.0);
+        map.for_each_in_radius(&rec.p, self.radius, |photon| {
+            let cos_theta = -Vec3::dot(&rec.normal, &photon.direction);
+            if cos_theta <= 0.0 {
+                return;
+            }
+            let wi = Ray::new(rec.p, -photon.direction, r.time());
+            sum += rec.mat.scattering_value(r, rec, &wi) / cos_theta * photon.power;
+        });
+        sum / (rtweekend::PI_F64 * self.radius * self.radius)
+    }
+}
@@ -0,0 +1,301 @@
+//! # `principled.rs` 模块说明
+//!
+//! 参考 Disney principled BSDF（Burley 2012, 2015）的综合材质。所有参数都是纹理，
+//! 标量参数取纹理颜色的第一个分量，取值范围均为 [0, 1]：
+//! - `base_color`：漫反射颜色；金属的镜面反射颜色
+//! - `metallic`：0 为电介质，1 为金属，中间值在两者之间线性混合
+//! - `roughness`：漫反射的逆反射项与 GGX 镜面波瓣共用，至少为 `MIN_ROUGHNESS`
+//! - `specular`、`specular_tint`：电介质的法向反射率为 0.08·specular，并按 specular_tint 偏向基础色的色调
+//! - `sheen`：掠射角处的白色光泽，用于布料
+//! - `clearcoat`、`clearcoat_gloss`：独立的 GTR1 清漆波瓣，法向反射率 0.04，光泽度决定其粗糙程度
+//! - `transmission`：粗糙电介质透射（折射率 1.5）所占的比例，穿过物体一进一出后染上基础色
+//! - `emission`：正面的自发光
+//!
+//! 各波瓣按大致的能量占比选择采样，概率密度按相同的权重混合（`WeightedMixturePdf`），
+//! 因此和其他材质一样可以与光源采样一起做 MIS。
+
+use std::sync::Arc;
+
+use crate::hittable::HitRecord;
+use crate::material::{Material, ScatterRecord};
+use crate::microfacet::{self, rough_dielectric, DielectricMicrofacetPdf, MicrofacetPdf, TrowbridgeReitz};
+use crate::onb::Onb;
+use crate::pdf::{CosinePdf, Pdf, WeightedMixturePdf};
+use crate::ray::Ray;
+use crate::rtweekend::{self, random_double};
+use crate::texture::{SolidColor, Texture};
+use crate::vec3::{Color, Point3, Vec3};
+
+/// 粗糙度的下限：更光滑的波瓣无法与光源采样做 MIS
+pub const MIN_ROUGHNESS: f64 = 0.05;
+
+const TRANSMISSION_IOR: f64 = 1.5;
+
+#[derive(Debug)]
+pub struct Principled {
+    pub base_color: Arc<dyn Texture>,
+    pub metallic: Arc<dyn Texture>,
+    pub roughness: Arc<dyn Texture>,
+    pub specular: Arc<dyn Texture>,
+    pub specular_tint: Arc<dyn Texture>,
+    pub sheen: Arc<dyn Texture>,
+    pub clearcoat: Arc<dyn Texture>,
+    pub clearcoat_gloss: Arc<dyn Texture>,
+    pub transmission: Arc<dyn Texture>,
+    pub emission: Arc<dyn Texture>,
+}
+
+/// 各分量都为 `value` 的纯色纹理，用来给标量参数赋值
+pub fn constant(value: f64) -> Arc<dyn Texture> {
+    Arc::new(SolidColor::from_rgb(value, value, value))
+}
+
+impl Principled {
+    /// 基础色为 `base_color` 的不透明电介质（类似塑料），其余参数取默认值
+    pub fn new(base_color: Color) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(base_color)))
+    }
+
+    pub fn from_texture(base_color: Arc<dyn Texture>) -> Self {
+        Self {
+            base_color,
+            metallic: constant(0.0),
+            roughness: constant(0.5),
+            specular: constant(0.5),
+            specular_tint: constant(0.0),
+            sheen: constant(0.0),
+            clearcoat: constant(0.0),
+            clearcoat_gloss: constant(1.0),
+            transmission: constant(0.0),
+            emission: constant(0.0),
+        }
+    }
+
+    //  在交点处读取所有纹理
+    fn bsdf(&self, rec: &HitRecord) -> PrincipledBsdf {
+        let scalar = |tex: &Arc<dyn Texture>| tex.value(rec.u, rec.v, &rec.p).x().clamp(0.0, 1.0);
+        let base_color = self.base_color.value(rec.u, rec.v, &rec.p);
+        let metallic = scalar(&self.metallic);
+        let roughness = scalar(&self.roughness).max(MIN_ROUGHNESS);
+        let specular_tint = scalar(&self.specular_tint);
+
+        //  色调：去掉亮度后的基础色
+        let luminance = 0.3 * base_color.x() + 0.6 * base_color.y() + 0.1 * base_color.z();
+        let tint = if luminance > 0.0 {
+            base_color / luminance
+        } else {
+            Color::new(1.0, 1.0, 1.0)
+        };
+        let white = Color::new(1.0, 1.0, 1.0);
+        let dielectric_f0 = 0.08 * scalar(&self.specular) * lerp(white, tint, specular_tint);
+
+        PrincipledBsdf {
+            base_color,
+            metallic,
+            roughness,
+            specular_f0: lerp(dielectric_f0, base_color, metallic),
+            sheen: scalar(&self.sheen),
+            clearcoat: scalar(&self.clearcoat),
+            clearcoat_alpha: 0.1 + (0.001 - 0.1) * scalar(&self.clearcoat_gloss),
+            transmission: scalar(&self.transmission),
+            eta: if rec.front_face {
+                TRANSMISSION_IOR
+            } else {
+                1.0 / TRANSMISSION_IOR
+            },
+            distribution: TrowbridgeReitz::from_roughness(roughness, roughness),
+        }
+    }
+}
+
+fn lerp(a: Color, b: Color, t: f64) -> Color {
+    (1.0 - t) * a + t * b
+}
+
+fn schlick_weight(cos_theta: f64) -> f64 {
+    (1.0 - cos_theta).clamp(0.0, 1.0).powi(5)
+}
+
+//  GTR1 法线分布，只用于清漆波瓣
+fn gtr1(cos_theta_h: f64, alpha: f64) -> f64 {
+    let a2 = alpha * alpha;
+    let t = 1.0 + (a2 - 1.0) * cos_theta_h * cos_theta_h;
+    (a2 - 1.0) / (rtweekend::PI_F64 * a2.ln() * t)
+}
+
+//  一个交点处的全部参数
+#[derive(Debug, Clone, Copy)]
+struct PrincipledBsdf {
+    base_color: Color,
+    metallic: f64,
+    roughness: f64,
+    specular_f0: Color,
+    sheen: f64,
+    clearcoat: f64,
+    clearcoat_alpha: f64,
+    transmission: f64,
+    eta: f64, //  法线背面与正面的折射率之比
+    distribution: TrowbridgeReitz,
+}
+
+impl PrincipledBsdf {
+    /// 局部坐标系中的 f·|cosθi|
+    fn evaluate(&self, wo: &Vec3, wi: &Vec3) -> Color {
+        if wo.z() <= 0.0 || wi.z() == 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        if wi.z() < 0.0 {
+            //  透射：颜色取基础色的平方根，进出一次后恰好为基础色
+            let weight = (1.0 - self.metallic) * self.transmission;
+            if weight <= 0.0 {
+                return Color::new(0.0, 0.0, 0.0);
+            }
+            let (value, _) = rough_dielectric(&self.distribution, wo, wi, self.eta);
+            let tint = Color::new(
+                self.base_color.x().sqrt(),
+                self.base_color.y().sqrt(),
+                self.base_color.z().sqrt(),
+            );
+            return tint * (weight * value);
+        }
+
+        let h = Vec3::unit_vector(&(*wo + *wi));
+        let cos_d = Vec3::dot(wi, &h);
+
+        //  Burley 漫反射，粗糙表面在掠射角处有逆反射
+        let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+        let fl = schlick_weight(wi.z());
+        let fv = schlick_weight(wo.z());
+        let diffuse = self.base_color
+            * ((1.0 + (fd90 - 1.0) * fl) * (1.0 + (fd90 - 1.0) * fv) / rtweekend::PI_F64);
+        let sheen = self.sheen * schlick_weight(cos_d);
+        let diffuse_weight = (1.0 - self.metallic) * (1.0 - self.transmission);
+        let mut value = (diffuse + Color::new(sheen, sheen, sheen)) * (diffuse_weight * wi.z());
+
+        //  GGX 镜面反射
+        let white = Color::new(1.0, 1.0, 1.0);
+        let fresnel = lerp(self.specular_f0, white, schlick_weight(cos_d));
+        let d = self.distribution.d(&h);
+        let g = self.distribution.g(wo, wi);
+        value += fresnel * (d * g / (4.0 * wo.z()));
+
+        //  清漆
+        if self.clearcoat > 0.0 {
+            let dr = gtr1(h.z(), self.clearcoat_alpha);
+            let fr = 0.04 + 0.96 * schlick_weight(cos_d);
+            let gr = clearcoat_masking().g(wo, wi);
+            let coat = 0.25 * self.clearcoat * dr * fr * gr / (4.0 * wo.z());
+            value += Color::new(coat, coat, coat);
+        }
+
+        value
+    }
+
+    //  漫反射、镜面反射、清漆与透射四个波瓣的采样权重
+    fn lobe_weights(&self) -> [f64; 4] {
+        [
+            (1.0 - self.metallic) * (1.0 - self.transmission),
+            0.5 * (1.0 + self.metallic),
+            0.25 * self.clearcoat,
+            (1.0 - self.metallic) * self.transmission,
+        ]
+    }
+
+    fn pdf(&self, normal: Vec3, wo: Vec3) -> WeightedMixturePdf {
+        let [diffuse, specular, clearcoat, transmission] = self.lobe_weights();
+        WeightedMixturePdf::new(vec![
+            (diffuse, Arc::new(CosinePdf::new(normal))),
+            (specular, Arc::new(MicrofacetPdf::new(normal, wo, self.distribution))),
+            (clearcoat, Arc::new(ClearcoatPdf::new(normal, wo, self.clearcoat_alpha))),
+            (
+                transmission,
+                Arc::new(DielectricMicrofacetPdf::new(normal, wo, self.eta, self.distribution)),
+            ),
+        ])
+    }
+}
+
+//  清漆波瓣的遮挡函数固定使用 α = 0.25 的 GGX
+fn clearcoat_masking() -> TrowbridgeReitz {
+    TrowbridgeReitz::from_roughness(0.5, 0.5)
+}
+
+//  按 GTR1 分布采样半程向量再镜面反射
+struct ClearcoatPdf {
+    uvw: Onb,
+    wo: Vec3,
+    alpha: f64,
+}
+
+impl ClearcoatPdf {
+    fn new(normal: Vec3, wo: Vec3, alpha: f64) -> Self {
+        let uvw = Onb::new(normal);
+        let wo = Vec3::unit_vector(&uvw.to_local(&wo));
+        Self { uvw, wo, alpha }
+    }
+}
+
+impl Pdf for ClearcoatPdf {
+    fn value(&self, direction: &Vec3) -> f64 {
+        let wi = Vec3::unit_vector(&self.uvw.to_local(direction));
+        let h = self.wo + wi;
+        if h.length_squared() == 0.0 {
+            return 0.0;
+        }
+        let h = Vec3::unit_vector(&h);
+        let wo_dot_h = Vec3::dot(&self.wo, &h);
+        if h.z() <= 0.0 || wo_dot_h <= 0.0 {
+            return 0.0;
+        }
+        gtr1(h.z(), self.alpha) * h.z() / (4.0 * wo_dot_h)
+    }
+
+    fn generate(&self) -> Vec3 {
+        let a2 = self.alpha * self.alpha;
+        let cos_theta = ((1.0 - a2.powf(1.0 - random_double())) / (1.0 - a2)).max(0.0).sqrt();
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * rtweekend::PI_F64 * random_double();
+        let h = Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+        self.uvw.transform(&microfacet::reflect(&self.wo, &h))
+    }
+}
+
+impl Material for Principled {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        if Vec3::dot(r_in.direction(), &rec.normal) >= 0.0 {
+            return None;
+        }
+        let bsdf = self.bsdf(rec);
+        Some(ScatterRecord {
+            attenuation: bsdf.base_color,
+            pdf_ptr: Some(Arc::new(bsdf.pdf(rec.normal, -*r_in.direction()))),
+            skip_pdf_ray: None,
+            null_collision: false,
+        })
+    }
+
+    fn emitted(&self, _r_in: &Ray, rec: &HitRecord, u: f64, v: f64, p: &Point3) -> Color {
+        if !rec.front_face {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        self.emission.value(u, v, p)
+    }
+
+    fn pdf_probability(&self, _r_in: &Ray, _rec: &HitRecord) -> f64 {
+        1.0
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.bsdf(rec)
+            .pdf(rec.normal, -*r_in.direction())
+            .value(scattered.direction())
+    }
+
+    fn scattering_value(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        let uvw = Onb::new(rec.normal);
+        let wo = Vec3::unit_vector(&uvw.to_local(&-*r_in.direction()));
+        let wi = Vec3::unit_vector(&uvw.to_local(scattered.direction()));
+        self.bsdf(rec).evaluate(&wo, &wi)
+    }
+}
@@ -1,6 +1,6 @@
 use crate::rtweekend;
 use crate::AABB::Aabb;
-use crate::hittable::{HitRecord, Hittable, HittableList};
+use crate::hittable::{HitRecord, Hittable, HittableList, ShadingCache};
 use crate::interval::Interval;
 use crate::material::Material;
 use crate::ray::Ray;
@@ -141,6 +141,10 @@ impl Hittable for Quad {
             mat: &*self.mat,
             u: 0.0,
             v: 0.0,
+            dpdu: self.u,
+            dpdv: self.v,
+            shading: ShadingCache::default(),
+            object: self as *const Self as usize,
         };
         if !self.is_interior(alpha, beta, &mut rec) {
             return None;
@@ -169,4 +173,39 @@ impl Hittable for Quad {
             + self.v * rtweekend::random_double();
         random_point - *origin
     }
+
+    fn sample_surface(&self, _time: f64) -> Option<(HitRecord<'_>, f64)> {
+        let a = rtweekend::random_double();
+        let b = rtweekend::random_double();
+        let rec = HitRecord {
+            p: self.q + a * self.u + b * self.v,
+            normal: self.normal,
+            t: 0.0,
+            front_face: true,
+            mat: &*self.mat,
+            u: a,
+            v: b,
+            dpdu: self.u,
+            dpdv: self.v,
+            shading: ShadingCache::default(),
+            object: self as *const Self as usize,
+        };
+        Some((rec, 1.0 / self.area))
+    }
+
+    fn surface_pdf(&self, p: &Point3, _time: f64) -> f64 {
+        //  点必须落在四边形所在平面上，且位于四边形内部
+        if (Vec3::dot(&self.normal, p) - self.d).abs() > 1e-4 * (1.0 + self.d.abs()) {
+            return 0.0;
+        }
+        let planar_hitpt_vector = *p - self.q;
+        let alpha = Vec3::dot(&self.w, &Vec3::cross(&planar_hitpt_vector, &self.v));
+        let beta = Vec3::dot(&self.w, &Vec3::cross(&self.u, &planar_hitpt_vector));
+        let unit_interval = Interval::new(0.0, 1.0);
+        if unit_interval.contains(alpha) && unit_interval.contains(beta) {
+            1.0 / self.area
+        } else {
+            0.0
+        }
+    }
 }
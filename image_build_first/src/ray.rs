@@ -1,10 +1,15 @@
-use crate::vec3::{Point3, Vec3};
+use crate::medium_stack::MediumStack;
+use crate::spectrum::{self, HERO_WAVELENGTHS};
+use crate::vec3::{Color, Point3, Vec3};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Ray {
     pub orig: Point3,
     pub dir: Vec3,
     tm: f64,
+    pub media: MediumStack, //  射线当前所在的电介质
+    pub wavelength: Option<f64>, //  路径遇到色散材质后选定的波长（纳米），之前为 None
+    pub wavelengths: Option<[f64; HERO_WAVELENGTHS]>, //  光谱模式下路径携带的 hero 波长
 }
 
 impl Ray {
@@ -13,6 +18,21 @@ impl Ray {
             orig: origi,
             dir: direct,
             tm: timer,
+            media: MediumStack::new(),
+            wavelength: None,
+            wavelengths: None,
+        }
+    }
+
+    /// 从 `origin` 沿 `direction` 继续同一条路径的射线：继承时间、所在的介质与波长
+    pub fn scattered(&self, origin: Point3, direction: Vec3) -> Self {
+        Self {
+            orig: origin,
+            dir: direction,
+            tm: self.tm,
+            media: self.media,
+            wavelength: self.wavelength,
+            wavelengths: self.wavelengths,
         }
     }
 
@@ -21,6 +41,9 @@ impl Ray {
             orig: origi,
             dir: direct,
             tm: 0.0,
+            media: MediumStack::new(),
+            wavelength: None,
+            wavelengths: None,
         }
     }
 
@@ -39,6 +62,22 @@ impl Ray {
     pub fn time(&self) -> f64 {
         self.tm
     }
+
+    /// 射线从起点走到参数 `t` 处时，被所在的吸收性电介质衰减后剩下的比例（已换算到射线的波长上）
+    pub fn medium_transmittance(&self, t: f64) -> Color {
+        if self.media.is_empty() {
+            return Color::new(1.0, 1.0, 1.0);
+        }
+        self.spectrum(&self.media.transmittance(t * self.dir.length()))
+    }
+
+    /// RGB 颜色在这条射线上的取值：光谱模式下升采样到携带的 hero 波长上，否则原样返回
+    pub fn spectrum(&self, c: &Color) -> Color {
+        match &self.wavelengths {
+            Some(lambdas) => spectrum::rgb_to_spectrum(c, lambdas),
+            None => *c,
+        }
+    }
 }
 
 pub fn hit_sphere(center: &Point3, radius: f64, r: &Ray) -> f64 {
@@ -1,5 +1,8 @@
 use rand::Rng;
+use std::cell::RefCell;
 use std::f64::consts::PI;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 // 常量定义
 pub const INFINITY_F64: f64 = f64::INFINITY;
@@ -13,18 +16,88 @@ pub fn degrees_to_radians(degrees: f64) -> f64 {
 // 重新导出其他模块里用到的类型
 pub use crate::vec3::{Color, Point3, Vec3};
 
+/// 随机数的来源。Metropolis 光传输需要控制渲染过程中用到的每一个随机数，
+/// 可以为当前线程设置一个来源来代替默认的线程随机数生成器
+pub trait SampleSource {
+    /// 返回 [0,1) 之间的下一个样本
+    fn next_sample(&mut self) -> f64;
+}
+
+thread_local! {
+    static SAMPLE_SOURCE: RefCell<Option<Rc<RefCell<dyn SampleSource>>>> = const { RefCell::new(None) };
+}
+
+//  所有线程上已设置的随机数来源个数。为 0 时 `random_double` 不查询线程局部变量，
+//  不使用 Metropolis 时与直接调用线程随机数生成器的开销相同
+static ACTIVE_SOURCES: AtomicUsize = AtomicUsize::new(0);
+
+/// 设置（或用 None 清除）当前线程的随机数来源
+pub fn set_sample_source(source: Option<Rc<RefCell<dyn SampleSource>>>) {
+    let installed = source.is_some();
+    let previous = SAMPLE_SOURCE.with(|current| std::mem::replace(&mut *current.borrow_mut(), source));
+    match (previous.is_some(), installed) {
+        (false, true) => {
+            ACTIVE_SOURCES.fetch_add(1, Ordering::SeqCst);
+        }
+        (true, false) => {
+            ACTIVE_SOURCES.fetch_sub(1, Ordering::SeqCst);
+        }
+        _ => {}
+    }
+}
+
 // 生成[0,1)之间的随机浮点数
 pub fn random_double() -> f64 {
-    let mut rng = rand::thread_rng();
-    rng.r#gen::<f64>()
+    if ACTIVE_SOURCES.load(Ordering::Relaxed) == 0 {
+        return rand::thread_rng().r#gen::<f64>();
+    }
+    let sample = SAMPLE_SOURCE.with(|current| {
+        current
+            .borrow()
+            .as_ref()
+            .map(|source| source.borrow_mut().next_sample())
+    });
+    match sample {
+        Some(sample) => sample,
+        None => {
+            let mut rng = rand::thread_rng();
+            rng.r#gen::<f64>()
+        }
+    }
 }
 
+//  以下都由 random_double 导出，保证设置了随机数来源时同样生效
 pub fn random_double_range(min: f64, max: f64) -> f64 {
-    let mut rng = rand::thread_rng();
-    rng.gen_range(min..max)
+    min + (max - min) * random_double()
 }
 
 pub fn random_int(min: usize, max: usize) -> usize {
-    let mut rng = rand::thread_rng();
-    rng.gen_range(min..=max)
+    let n = (max - min + 1) as f64;
+    (min + (random_double() * n) as usize).min(max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Constant(f64);
+
+    impl SampleSource for Constant {
+        fn next_sample(&mut self) -> f64 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn sample_source_replaces_the_thread_rng_until_cleared() {
+        set_sample_source(Some(Rc::new(RefCell::new(Constant(0.25)))));
+        assert_eq!(random_double(), 0.25);
+        assert_eq!(random_int(0, 3), 1);
+        //  替换已有的来源不重复计数
+        set_sample_source(Some(Rc::new(RefCell::new(Constant(0.75)))));
+        assert_eq!(random_double(), 0.75);
+        set_sample_source(None);
+        set_sample_source(None);
+        assert!((0..8).map(|_| random_double()).any(|x| x != 0.75));
+    }
 }
@@ -0,0 +1,352 @@
+//! # `sky.rs` 模块说明
+//!
+//! 基于 Preetham 解析天空模型（"A Practical Analytic Model for Daylight", 1999）的室外天空与太阳。
+//!
+//! - 天空亮度由 Perez 分布函数给出，参数由太阳天顶角与大气浑浊度（turbidity）决定
+//! - 太阳被建模为一个有角半径的圆盘光源，颜色按大气的瑞利散射与气溶胶消光衰减
+//! - `Sky` 既可以作为相机的背景（`Camera::sky`），也可以放进 `lights` 列表作为被采样的光源：
+//!   方向按太阳圆锥与朝向天顶的余弦分布混合采样，混合比例取两者在水平面上产生的照度之比
+//! - `sky_scale` 同时缩放天空与太阳，改变它不会破坏两者的平衡
+//!
+//! 世界坐标中 +y 为天顶；方位角从 +z 轴起算，朝 +x 方向为正。
+
+use crate::AABB::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::rtweekend::{self, random_double};
+use crate::vec3::{Color, Point3, Vec3};
+
+/// 太阳的默认角半径（度），与真实太阳接近
+const DEFAULT_SUN_RADIUS: f64 = 0.27;
+/// 默认的天空亮度缩放，把 kcd/m² 量级的亮度换算到渲染常用的范围
+const DEFAULT_SKY_SCALE: f64 = 0.05;
+/// 默认的太阳辐照度（太阳垂直照射时在表面上产生的照度）
+const DEFAULT_SUN_IRRADIANCE: f64 = 3.0;
+/// 作为光源采样时，太阳圆锥与天空各自至少占的比例
+const MIN_LOBE_WEIGHT: f64 = 0.1;
+
+fn luminance(c: &Color) -> f64 {
+    0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
+}
+
+pub struct Sky {
+    sun_direction: Vec3,
+    theta_sun: f64,
+    perez_y: [f64; 5],
+    perez_x: [f64; 5],
+    perez_yy: [f64; 5],
+    zenith: Vec3, //  天顶处的 (Y, x, y)
+    cos_sun_max: f64,
+    sun_radiance: Color, //  未乘 `sky_scale` 的太阳辐亮度
+    sun_weight: f64,     //  作为光源采样时选择太阳圆锥的概率
+    pub sky_scale: f64,
+    pub ground_albedo: Color, //  地平线以下视为漫反射地面，反射天空的亮度
+}
+
+impl Sky {
+    /// `elevation`、`azimuth` 为太阳的高度角与方位角（度），`turbidity` 一般取 2（晴朗）到 10（雾霾）
+    pub fn new(elevation: f64, azimuth: f64, turbidity: f64) -> Self {
+        Self::new_with_sun(
+            elevation,
+            azimuth,
+            turbidity,
+            DEFAULT_SUN_RADIUS,
+            DEFAULT_SUN_IRRADIANCE,
+        )
+    }
+
+    /// `sun_radius` 为太阳圆盘的角半径（度），`sun_irradiance` 为默认 `sky_scale` 下大气层外太阳垂直照射时的照度
+    pub fn new_with_sun(
+        elevation: f64,
+        azimuth: f64,
+        turbidity: f64,
+        sun_radius: f64,
+        sun_irradiance: f64,
+    ) -> Self {
+        let elevation_rad = rtweekend::degrees_to_radians(elevation.clamp(0.0, 90.0));
+        let azimuth_rad = rtweekend::degrees_to_radians(azimuth);
+        let sun_direction = Vec3::new(
+            elevation_rad.cos() * azimuth_rad.sin(),
+            elevation_rad.sin(),
+            elevation_rad.cos() * azimuth_rad.cos(),
+        );
+        let theta_sun = rtweekend::PI_F64 / 2.0 - elevation_rad;
+        let t = turbidity.max(1.0);
+
+        let perez_y = [
+            0.1787 * t - 1.4630,
+            -0.3554 * t + 0.4275,
+            -0.0227 * t + 5.3251,
+            0.1206 * t - 2.5771,
+            -0.0670 * t + 0.3703,
+        ];
+        let perez_x = [
+            -0.0193 * t - 0.2592,
+            -0.0665 * t + 0.0008,
+            -0.0004 * t + 0.2125,
+            -0.0641 * t - 0.8989,
+            -0.0033 * t + 0.0452,
+        ];
+        let perez_yy = [
+            -0.0167 * t - 0.2608,
+            -0.0950 * t + 0.0092,
+            -0.0079 * t + 0.2102,
+            -0.0441 * t - 1.6537,
+            -0.0109 * t + 0.0529,
+        ];
+
+        //  天顶亮度（kcd/m²）与色度
+        let chi = (4.0 / 9.0 - t / 120.0) * (rtweekend::PI_F64 - 2.0 * theta_sun);
+        let zenith_luminance = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+        let th = [theta_sun.powi(3), theta_sun.powi(2), theta_sun, 1.0];
+        let chroma = |m: [[f64; 4]; 3]| {
+            let row = |r: [f64; 4]| r[0] * th[0] + r[1] * th[1] + r[2] * th[2] + r[3] * th[3];
+            t * t * row(m[0]) + t * row(m[1]) + row(m[2])
+        };
+        let zenith_x = chroma([
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let zenith_y = chroma([
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+
+        //  太阳圆盘：辐照度除以立体角得到辐亮度，再乘以大气透射率；
+        //  换算到与天空相同的单位，之后和天空一起乘以 `sky_scale`
+        let cos_sun_max = rtweekend::degrees_to_radians(sun_radius.max(1e-3)).cos();
+        let solid_angle = 2.0 * rtweekend::PI_F64 * (1.0 - cos_sun_max);
+        let sun_radiance =
+            sun_irradiance / solid_angle / DEFAULT_SKY_SCALE * Self::sun_transmittance(theta_sun, t);
+
+        let mut sky = Self {
+            sun_direction,
+            theta_sun,
+            perez_y,
+            perez_x,
+            perez_yy,
+            zenith: Vec3::new(zenith_luminance, zenith_x, zenith_y),
+            cos_sun_max,
+            sun_radiance,
+            sun_weight: 0.5,
+            sky_scale: DEFAULT_SKY_SCALE,
+            ground_albedo: Color::new(0.3, 0.3, 0.3),
+        };
+        sky.sun_weight = sky.sun_sampling_weight();
+        sky
+    }
+
+    pub fn sun_direction(&self) -> Vec3 {
+        self.sun_direction
+    }
+
+    /// 沿 `direction` 方向看到的天空辐亮度（包含太阳圆盘）
+    pub fn value(&self, direction: &Vec3) -> Color {
+        let d = Vec3::unit_vector(direction);
+        if d.y() <= 0.0 {
+            let mirrored = Vec3::new(d.x(), -d.y(), d.z());
+            return self.ground_albedo * self.sky_radiance(&mirrored);
+        }
+
+        let mut color = self.sky_radiance(&d);
+        if Vec3::dot(&d, &self.sun_direction) >= self.cos_sun_max {
+            color += self.sky_scale * self.sun_radiance;
+        }
+        color
+    }
+
+    //  太阳与天空在水平面上产生的照度之比，决定作为光源采样时两部分的混合比例；
+    //  两者都乘以 `sky_scale`，比例与它无关。天空的照度用 16×32 的中点求积估计
+    fn sun_sampling_weight(&self) -> f64 {
+        let solid_angle = 2.0 * rtweekend::PI_F64 * (1.0 - self.cos_sun_max);
+        let sun = luminance(&self.sun_radiance) * self.sky_scale * solid_angle * self.sun_direction.y().max(0.0);
+
+        let (n_theta, n_phi) = (16, 32);
+        let d_phi = 2.0 * rtweekend::PI_F64 / n_phi as f64;
+        let mut sky = 0.0;
+        for i in 0..n_theta {
+            //  以 cosθ 为变量，dω = d(cosθ) dφ
+            let cos_theta = (i as f64 + 0.5) / n_theta as f64;
+            let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+            for j in 0..n_phi {
+                let phi = (j as f64 + 0.5) * d_phi;
+                let d = Vec3::new(sin_theta * phi.sin(), cos_theta, sin_theta * phi.cos());
+                sky += luminance(&self.sky_radiance(&d)) * cos_theta / n_theta as f64 * d_phi;
+            }
+        }
+
+        if sun + sky <= 0.0 {
+            return 0.5;
+        }
+        (sun / (sun + sky)).clamp(MIN_LOBE_WEIGHT, 1.0 - MIN_LOBE_WEIGHT)
+    }
+
+    //  太阳圆锥内均匀分布的概率密度
+    fn sun_pdf(&self, direction: &Vec3) -> f64 {
+        let cos_theta = Vec3::dot(&Vec3::unit_vector(direction), &self.sun_direction);
+        if cos_theta < self.cos_sun_max {
+            return 0.0;
+        }
+        1.0 / (2.0 * rtweekend::PI_F64 * (1.0 - self.cos_sun_max))
+    }
+
+    //  朝向天顶的余弦分布的概率密度，地平线以下为 0
+    fn dome_pdf(direction: &Vec3) -> f64 {
+        Vec3::unit_vector(direction).y().max(0.0) / rtweekend::PI_F64
+    }
+
+    //  不含太阳圆盘的天空辐亮度，`d` 为单位向量且位于地平线以上
+    fn sky_radiance(&self, d: &Vec3) -> Color {
+        let theta = d.y().clamp(0.0, 1.0).acos();
+        let cos_gamma = Vec3::dot(d, &self.sun_direction).clamp(-1.0, 1.0);
+        let gamma = cos_gamma.acos();
+
+        let luminance = self.zenith.x() * Self::perez(&self.perez_y, theta, gamma)
+            / Self::perez(&self.perez_y, 0.0, self.theta_sun);
+        let x = self.zenith.y() * Self::perez(&self.perez_x, theta, gamma)
+            / Self::perez(&self.perez_x, 0.0, self.theta_sun);
+        let y = self.zenith.z() * Self::perez(&self.perez_yy, theta, gamma)
+            / Self::perez(&self.perez_yy, 0.0, self.theta_sun);
+
+        self.sky_scale * Self::yxy_to_rgb(luminance, x, y)
+    }
+
+    //  Perez 分布函数 F(θ, γ)
+    fn perez(c: &[f64; 5], theta: f64, gamma: f64) -> f64 {
+        let cos_theta = theta.cos().max(0.01);
+        let cos_gamma = gamma.cos();
+        (1.0 + c[0] * (c[1] / cos_theta).exp())
+            * (1.0 + c[2] * (c[3] * gamma).exp() + c[4] * cos_gamma * cos_gamma)
+    }
+
+    fn yxy_to_rgb(luminance: f64, x: f64, y: f64) -> Color {
+        if y <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let cx = x / y * luminance;
+        let cz = (1.0 - x - y) / y * luminance;
+        let cy = luminance;
+        Color::new(
+            (3.2406 * cx - 1.5372 * cy - 0.4986 * cz).max(0.0),
+            (-0.9689 * cx + 1.8758 * cy + 0.0415 * cz).max(0.0),
+            (0.0557 * cx - 0.2040 * cy + 1.0570 * cz).max(0.0),
+        )
+    }
+
+    //  太阳光穿过大气后的 RGB 透射率：瑞利散射与气溶胶（Ångström 公式）消光
+    fn sun_transmittance(theta_sun: f64, turbidity: f64) -> Color {
+        let theta_deg = theta_sun.to_degrees().min(93.0);
+        let air_mass = 1.0 / (theta_sun.cos() + 0.15 * (93.885 - theta_deg).powf(-1.253));
+        let beta = 0.04608 * turbidity - 0.04586;
+        let alpha = 1.3;
+
+        let channel = |lambda_um: f64| {
+            let rayleigh = (-0.008735 * lambda_um.powf(-4.08) * air_mass).exp();
+            let aerosol = (-beta * lambda_um.powf(-alpha) * air_mass).exp();
+            rayleigh * aerosol
+        };
+        Color::new(channel(0.680), channel(0.550), channel(0.440))
+    }
+}
+
+//  作为光源时，太阳与天空都位于无穷远处：不与射线求交，只负责方向采样。
+//  地平线以下的地面反射只能由 BSDF 采样得到
+impl Hittable for Sky {
+    fn hit<'a>(&'a self, _r: &Ray, _ray_t: &Interval) -> Option<HitRecord<'a>> {
+        None
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::empty()
+    }
+
+    fn pdf_value(&self, _origin: &Point3, direction: &Vec3) -> f64 {
+        self.sun_weight * self.sun_pdf(direction) + (1.0 - self.sun_weight) * Self::dome_pdf(direction)
+    }
+
+    fn random(&self, _origin: &Point3) -> Vec3 {
+        if random_double() >= self.sun_weight {
+            return Onb::new(Vec3::new(0.0, 1.0, 0.0)).transform(&Vec3::random_cosine_direction());
+        }
+
+        //  在以太阳方向为轴的圆锥内均匀采样
+        let z = 1.0 + random_double() * (self.cos_sun_max - 1.0);
+        let phi = 2.0 * rtweekend::PI_F64 * random_double();
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let uvw = Onb::new(self.sun_direction);
+        uvw.transform(&Vec3::new(phi.cos() * r, phi.sin() * r, z))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //  以 `axis` 为轴的 (cosθ, φ) 中点求积，cosθ 在 `split` 处分段，使很窄的太阳圆锥边缘落在格子边界上
+    fn integrate(axis: Vec3, split: f64, f: impl Fn(&Vec3) -> f64) -> f64 {
+        let uvw = Onb::new(axis);
+        let (n_z, n_phi) = (4000, 64);
+        let d_phi = 2.0 * rtweekend::PI_F64 / n_phi as f64;
+        let mut sum = 0.0;
+        //  [-1, split] 与 [split, 1] 两段分别均匀划分
+        for (lo, hi) in [(-1.0, split), (split, 1.0)] {
+            let dz = (hi - lo) / n_z as f64;
+            for i in 0..n_z {
+                let z = lo + (i as f64 + 0.5) * dz;
+                let r = (1.0 - z * z).sqrt();
+                for j in 0..n_phi {
+                    let phi = (j as f64 + 0.5) * d_phi;
+                    let d = uvw.transform(&Vec3::new(r * phi.cos(), r * phi.sin(), z));
+                    sum += f(&d) * dz * d_phi;
+                }
+            }
+        }
+        sum
+    }
+
+    #[test]
+    fn sampling_pdfs_integrate_to_one() {
+        let sky = Sky::new_with_sun(40.0, 30.0, 3.0, 2.0, DEFAULT_SUN_IRRADIANCE);
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        assert!((integrate(sky.sun_direction(), sky.cos_sun_max, |d| sky.sun_pdf(d)) - 1.0).abs() < 1e-3);
+        assert!((integrate(sky.sun_direction(), sky.cos_sun_max, |d| sky.pdf_value(&origin, d)) - 1.0).abs() < 1e-3);
+        //  采样得到的方向都落在密度为正的区域
+        for _ in 0..1000 {
+            assert!(sky.pdf_value(&origin, &sky.random(&origin)) > 0.0);
+        }
+    }
+
+    #[test]
+    fn sun_and_sky_share_the_scale() {
+        let mut sky = Sky::new(40.0, 30.0, 3.0);
+        let (sun, dome) = (sky.value(&sky.sun_direction()), sky.value(&Vec3::new(0.0, 1.0, 0.0)));
+        sky.sky_scale *= 2.0;
+        assert!((sky.value(&sky.sun_direction()) - 2.0 * sun).length() < 1e-9 * sun.length());
+        assert!((sky.value(&Vec3::new(0.0, 1.0, 0.0)) - 2.0 * dome).length() < 1e-9 * dome.length());
+        //  两个采样分支的比例只取决于太阳与天空的相对照度，与整体亮度无关
+        let weight = sky.sun_weight;
+        assert_eq!(sky.sun_sampling_weight(), weight);
+        assert!((MIN_LOBE_WEIGHT..=1.0 - MIN_LOBE_WEIGHT).contains(&weight));
+    }
+
+    #[test]
+    fn zenith_radiance_follows_sun_elevation_and_turbidity() {
+        let zenith = Vec3::new(0.0, 1.0, 0.0);
+        let brightness = |elevation: f64, turbidity: f64| luminance(&Sky::new(elevation, 0.0, turbidity).value(&zenith));
+
+        //  太阳越高天顶越亮，浑浊的大气散射更多的光
+        let elevations = [5.0, 20.0, 40.0, 60.0, 80.0];
+        for pair in elevations.windows(2) {
+            assert!(brightness(pair[0], 3.0) < brightness(pair[1], 3.0), "{:?}", pair);
+        }
+        assert!(brightness(45.0, 2.0) < brightness(45.0, 6.0));
+
+        //  晴朗天空的天顶偏蓝
+        let clear = Sky::new(45.0, 0.0, 2.5).value(&zenith);
+        assert!(clear.z() > clear.x(), "{:?}", clear);
+    }
+}
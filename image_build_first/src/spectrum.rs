@@ -0,0 +1,218 @@
+//! # `spectrum.rs` 模块说明
+//!
+//! 单一波长与 RGB 之间的换算，供色散等与波长有关的效果使用。
+//!
+//! - CIE 1931 配色函数使用 Wyman, Sloan & Shirley (2013) 的多段高斯拟合
+//! - XYZ 转换到线性 sRGB，超出色域的负分量截断为 0
+//! - `sample_wavelength` 在可见光范围内均匀采样一个波长，并给出对应的 RGB 权重；
+//!   权重已归一化，对所有波长求平均恰好为白色 (1, 1, 1)，因此不涉及色散的部分照常按 RGB 计算
+//!
+//! 光谱渲染模式（`Camera::spectral`）使用 hero 波长采样（Wilkie et al. 2014）：
+//! - 每条相机路径携带 `HERO_WAVELENGTHS` 个波长，第一个均匀采样，其余在可见光范围内等距错开，
+//!   `Color` 的三个分量此时依次是这几个波长上的值
+//! - RGB 反射率与发光通过 `rgb_to_spectrum` 升采样为光滑光谱：红、绿、蓝三个基函数由 logistic 曲线构成，
+//!   在每个波长上相加恰好为 1，因此白色升采样后是等能光谱，且反射率不会超过 1
+//! - 路径结束后用 `spectrum_to_rgb` 按配色函数积分为 XYZ 再转换到 sRGB，等能光谱对应白色 (1, 1, 1)
+//!
+//! `blackbody_rgb` 给出指定色温的黑体辐射颜色，同样以等能光谱为白色，并把亮度归一化为 1
+
+use crate::rtweekend::random_double;
+use crate::vec3::{Color, Vec3};
+
+use std::sync::OnceLock;
+
+pub const LAMBDA_MIN: f64 = 380.0;
+pub const LAMBDA_MAX: f64 = 780.0;
+
+/// 光谱模式下每条路径携带的波长数，与 `Color` 的分量数相同
+pub const HERO_WAVELENGTHS: usize = 3;
+
+//  RGB 基函数在蓝绿、绿红之间过渡的中心波长与宽度（纳米）
+const BLUE_GREEN_EDGE: f64 = 490.0;
+const GREEN_RED_EDGE: f64 = 585.0;
+const EDGE_WIDTH: f64 = 8.0;
+
+//  左右两侧宽度不同的高斯函数
+fn piecewise_gaussian(x: f64, mu: f64, sigma_left: f64, sigma_right: f64) -> f64 {
+    let sigma = if x < mu { sigma_left } else { sigma_right };
+    let t = (x - mu) / sigma;
+    (-0.5 * t * t).exp()
+}
+
+/// 波长 `lambda`（纳米）处的 CIE 1931 配色函数 x̄、ȳ、z̄
+pub fn cie_xyz(lambda: f64) -> Vec3 {
+    let x = 1.056 * piecewise_gaussian(lambda, 599.8, 37.9, 31.0)
+        + 0.362 * piecewise_gaussian(lambda, 442.0, 16.0, 26.7)
+        - 0.065 * piecewise_gaussian(lambda, 501.1, 20.4, 26.2);
+    let y = 0.821 * piecewise_gaussian(lambda, 568.8, 46.9, 40.5)
+        + 0.286 * piecewise_gaussian(lambda, 530.9, 16.3, 31.1);
+    let z = 1.217 * piecewise_gaussian(lambda, 437.0, 11.8, 36.0)
+        + 0.681 * piecewise_gaussian(lambda, 459.0, 26.0, 13.8);
+    Vec3::new(x, y, z)
+}
+
+/// CIE XYZ 转换到线性 sRGB（D65 白点）
+pub fn xyz_to_rgb(xyz: &Vec3) -> Color {
+    Color::new(
+        3.2406 * xyz.x() - 1.5372 * xyz.y() - 0.4986 * xyz.z(),
+        -0.9689 * xyz.x() + 1.8758 * xyz.y() + 0.0415 * xyz.z(),
+        0.0557 * xyz.x() - 0.2040 * xyz.y() + 1.0570 * xyz.z(),
+    )
+}
+
+/// 单一波长的光对应的线性 sRGB 颜色，负分量截断为 0
+pub fn wavelength_to_rgb(lambda: f64) -> Color {
+    let rgb = xyz_to_rgb(&cie_xyz(lambda));
+    Color::new(rgb.x().max(0.0), rgb.y().max(0.0), rgb.z().max(0.0))
+}
+
+//  `wavelength_to_rgb` 在可见光范围内的平均值，用来把权重归一化为白色
+fn average_rgb() -> Color {
+    static AVERAGE: OnceLock<Color> = OnceLock::new();
+    *AVERAGE.get_or_init(|| {
+        let steps = 4000;
+        let mut sum = Color::new(0.0, 0.0, 0.0);
+        for i in 0..steps {
+            let lambda = LAMBDA_MIN + (i as f64 + 0.5) / steps as f64 * (LAMBDA_MAX - LAMBDA_MIN);
+            sum += wavelength_to_rgb(lambda);
+        }
+        sum / steps as f64
+    })
+}
+
+/// 波长 `lambda` 的 RGB 权重：均匀采样波长时乘上它，期望为白色
+pub fn wavelength_weight(lambda: f64) -> Color {
+    let average = average_rgb();
+    let rgb = wavelength_to_rgb(lambda);
+    Color::new(
+        rgb.x() / average.x(),
+        rgb.y() / average.y(),
+        rgb.z() / average.z(),
+    )
+}
+
+/// 在可见光范围内均匀采样一个波长，返回波长与其 RGB 权重
+pub fn sample_wavelength() -> (f64, Color) {
+    let lambda = LAMBDA_MIN + random_double() * (LAMBDA_MAX - LAMBDA_MIN);
+    (lambda, wavelength_weight(lambda))
+}
+
+/// 为一条路径采样一组 hero 波长：第一个在可见光范围内均匀分布，其余等距错开并回绕
+pub fn sample_hero_wavelengths() -> [f64; HERO_WAVELENGTHS] {
+    hero_wavelengths(random_double())
+}
+
+//  第一个波长位于可见光范围内比例 `u` 处的一组 hero 波长
+fn hero_wavelengths(u: f64) -> [f64; HERO_WAVELENGTHS] {
+    let range = LAMBDA_MAX - LAMBDA_MIN;
+    let offset = u * range;
+    std::array::from_fn(|j| {
+        LAMBDA_MIN + (offset + j as f64 * range / HERO_WAVELENGTHS as f64) % range
+    })
+}
+
+fn logistic(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+/// RGB 颜色升采样后的光谱在波长 `lambda` 处的值
+pub fn rgb_to_spectrum_at(c: &Color, lambda: f64) -> f64 {
+    let blue = 1.0 - logistic((lambda - BLUE_GREEN_EDGE) / EDGE_WIDTH);
+    let red = logistic((lambda - GREEN_RED_EDGE) / EDGE_WIDTH);
+    let green = 1.0 - blue - red;
+    c.x() * red + c.y() * green + c.z() * blue
+}
+
+/// RGB 颜色在一组 hero 波长上的值
+pub fn rgb_to_spectrum(c: &Color, lambdas: &[f64; HERO_WAVELENGTHS]) -> Color {
+    Color::new(
+        rgb_to_spectrum_at(c, lambdas[0]),
+        rgb_to_spectrum_at(c, lambdas[1]),
+        rgb_to_spectrum_at(c, lambdas[2]),
+    )
+}
+
+//  配色函数在可见光范围内的积分换算成的 RGB，即等能光谱的颜色，用来把它归一化为白色
+fn equal_energy_rgb() -> Color {
+    static WHITE: OnceLock<Color> = OnceLock::new();
+    *WHITE.get_or_init(|| {
+        let steps = 4000;
+        let step = (LAMBDA_MAX - LAMBDA_MIN) / steps as f64;
+        let mut xyz = Vec3::new(0.0, 0.0, 0.0);
+        for i in 0..steps {
+            xyz += cie_xyz(LAMBDA_MIN + (i as f64 + 0.5) * step) * step;
+        }
+        xyz_to_rgb(&xyz)
+    })
+}
+
+/// 把 hero 波长上的辐亮度 `l` 换算为线性 sRGB：对配色函数做蒙特卡洛积分后转换，
+/// 各波长的概率密度均为 1 / (LAMBDA_MAX - LAMBDA_MIN)
+pub fn spectrum_to_rgb(l: &Color, lambdas: &[f64; HERO_WAVELENGTHS]) -> Color {
+    let mut xyz = Vec3::new(0.0, 0.0, 0.0);
+    for (j, lambda) in lambdas.iter().enumerate() {
+        xyz += cie_xyz(*lambda) * l[j];
+    }
+    xyz *= (LAMBDA_MAX - LAMBDA_MIN) / HERO_WAVELENGTHS as f64;
+    let rgb = xyz_to_rgb(&xyz);
+    let white = equal_energy_rgb();
+    Color::new(rgb.x() / white.x(), rgb.y() / white.y(), rgb.z() / white.z())
+}
+
+//  第二辐射常数 hc/k（纳米·开尔文）
+const PLANCK_C2: f64 = 1.438_776_9e7;
+
+//  温度为 `kelvin` 的黑体在波长 `lambda`（纳米）处的相对光谱辐亮度，省略了常数因子
+fn planck(lambda: f64, kelvin: f64) -> f64 {
+    1.0 / (lambda.powi(5) * ((PLANCK_C2 / (lambda * kelvin)).exp() - 1.0))
+}
+
+/// 色温为 `kelvin` 的黑体辐射对应的线性 sRGB 颜色，亮度归一化为 1，超出色域的负分量截断为 0
+pub fn blackbody_rgb(kelvin: f64) -> Color {
+    let steps = 400;
+    let step = (LAMBDA_MAX - LAMBDA_MIN) / steps as f64;
+    let mut xyz = Vec3::new(0.0, 0.0, 0.0);
+    for i in 0..steps {
+        let lambda = LAMBDA_MIN + (i as f64 + 0.5) * step;
+        xyz += cie_xyz(lambda) * planck(lambda, kelvin);
+    }
+    let rgb = xyz_to_rgb(&xyz);
+    let white = equal_energy_rgb();
+    let c = Color::new(
+        (rgb.x() / white.x()).max(0.0),
+        (rgb.y() / white.y()).max(0.0),
+        (rgb.z() / white.z()).max(0.0),
+    );
+    let luminance = 0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z();
+    if luminance > 0.0 { c / luminance } else { Color::new(0.0, 0.0, 0.0) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //  对第一个 hero 波长分层求 `estimate` 的期望
+    fn expectation(estimate: impl Fn(&[f64; HERO_WAVELENGTHS]) -> Color) -> Color {
+        let steps = 20000;
+        let mut sum = Color::new(0.0, 0.0, 0.0);
+        for i in 0..steps {
+            sum += estimate(&hero_wavelengths((i as f64 + 0.5) / steps as f64));
+        }
+        sum / steps as f64
+    }
+
+    #[test]
+    fn terminating_secondary_wavelengths_is_unbiased() {
+        let c = Color::new(0.8, 0.3, 0.1);
+        let all = expectation(|lambdas| spectrum_to_rgb(&rgb_to_spectrum(&c, lambdas), lambdas));
+        //  色散时只保留第一个波长并乘上 HERO_WAVELENGTHS（见 `Camera::shade`）
+        let hero = expectation(|lambdas| {
+            let l = rgb_to_spectrum_at(&c, lambdas[0]) * HERO_WAVELENGTHS as f64;
+            spectrum_to_rgb(&Color::new(l, 0.0, 0.0), lambdas)
+        });
+        for k in 0..3 {
+            assert!((all[k] - hero[k]).abs() < 1e-3, "{:?} != {:?}", all, hero);
+            assert!((all[k] - c[k]).abs() < 0.05, "{:?} != {:?}", all, c);
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use crate::AABB::Aabb;
-use crate::hittable::{HitRecord, Hittable};
+use crate::hittable::{HitRecord, Hittable, ShadingCache};
 use crate::interval::Interval;
 use crate::material::Material;
 use crate::ray::Ray;
@@ -56,6 +56,18 @@ impl Sphere {
         (phi / (2.0 * rtweekend::PI_F64), theta / rtweekend::PI_F64)
     }
 
+    //  单位球面上点 p 处的 (∂p/∂u, ∂p/∂v)，乘以半径即为球面上的切线；两极处切线退化为零
+    fn get_sphere_tangents(p: &Point3) -> (Vec3, Vec3) {
+        let dpdu = 2.0 * rtweekend::PI_F64 * Vec3::new(p.z(), 0.0, -p.x());
+        let sin_theta = (p.x() * p.x() + p.z() * p.z()).sqrt();
+        if sin_theta <= 0.0 {
+            return (dpdu, Vec3::new(0.0, 0.0, 0.0));
+        }
+        let dpdv = rtweekend::PI_F64
+            * Vec3::new(-p.x() * p.y() / sin_theta, sin_theta, -p.z() * p.y() / sin_theta);
+        (dpdu, dpdv)
+    }
+
     fn random_to_sphere(radius : f64, distance_square : f64) -> Vec3 {
         let r1 = rtweekend::random_double();
         let r2 = rtweekend::random_double();
@@ -97,6 +109,7 @@ impl Hittable for Sphere {
 
         // let mat = Arc::clone(&self.mat);
         let (u, v) = Self::get_sphere_uv(&outward_normal);
+        let (dpdu, dpdv) = Self::get_sphere_tangents(&outward_normal);
 
         let mut rec = HitRecord {
             p,
@@ -106,6 +119,10 @@ impl Hittable for Sphere {
             mat: &*self.mat,
             u,
             v,
+            dpdu: self.radius * dpdu,
+            dpdv: self.radius * dpdv,
+            shading: ShadingCache::default(),
+            object: self as *const Self as usize,
         };
         rec.set_face_normal(r, &outward_normal);
         Some(rec)
@@ -136,4 +153,53 @@ impl Hittable for Sphere {
         let uvw = Onb::new(direction);
         uvw.transform(&Sphere::random_to_sphere(self.radius, distance_squared))
     }
+
+    fn sample_surface(&self, time: f64) -> Option<(HitRecord<'_>, f64)> {
+        let outward_normal = Vec3::unit_vector(&Vec3::random_unit_vector());
+        let (u, v) = Self::get_sphere_uv(&outward_normal);
+        let (dpdu, dpdv) = Self::get_sphere_tangents(&outward_normal);
+        let rec = HitRecord {
+            p: self.center.at(time) + self.radius * outward_normal,
+            normal: outward_normal,
+            t: 0.0,
+            front_face: true,
+            mat: &*self.mat,
+            u,
+            v,
+            dpdu: self.radius * dpdu,
+            dpdv: self.radius * dpdv,
+            shading: ShadingCache::default(),
+            object: self as *const Self as usize,
+        };
+        Some((rec, 1.0 / (4.0 * rtweekend::PI_F64 * self.radius * self.radius)))
+    }
+
+    fn surface_pdf(&self, p: &Point3, time: f64) -> f64 {
+        let distance = (*p - self.center.at(time)).length();
+        if (distance - self.radius).abs() > 1e-4 * self.radius.max(1.0) {
+            return 0.0;
+        }
+        1.0 / (4.0 * rtweekend::PI_F64 * self.radius * self.radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::EmptyMaterial;
+
+    #[test]
+    fn moving_sphere_is_sampled_at_the_given_time() {
+        let center1 = Point3::new(0.0, 0.0, 0.0);
+        let center2 = Point3::new(10.0, 0.0, 0.0);
+        let sphere = Sphere::new_moving(center1, center2, 2.0, Arc::new(EmptyMaterial));
+        for &time in &[0.0, 0.3, 1.0] {
+            let (rec, pdf) = sphere.sample_surface(time).unwrap();
+            let center = center1 + time * (center2 - center1);
+            assert!(((rec.p - center).length() - 2.0).abs() < 1e-9);
+            assert_eq!(sphere.surface_pdf(&rec.p, time), pdf);
+        }
+        let (rec, _) = sphere.sample_surface(1.0).unwrap();
+        assert_eq!(sphere.surface_pdf(&rec.p, 0.0), 0.0);
+    }
 }
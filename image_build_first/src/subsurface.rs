@@ -0,0 +1,396 @@
+//! # `subsurface.rs` 模块说明
+//!
+//! 次表面散射：皮肤、蜡、大理石、牛奶。物体表面是光滑的电介质界面（与 `Dielectric` 相同，
+//! 参与嵌套介质的折射率计算），内部是各向同性散射的参与介质，光在内部随机游走后从别处射出。
+//!
+//! - 内部介质由平均自由程 `mean_free_path`（σ_t = 1 / 平均自由程，逐通道）与单次散射反照率 `albedo` 描述，
+//!   两者都是纹理，在内部点 p 处求值（u = v = 0），适合使用实体纹理；
+//!   红色通道的自由程较长时，红光会渗得更远，呈现皮肤的透光感
+//! - 自由程用 delta tracking 采样，主消光系数 σ̄ = 1 / `min_mean_free_path`（逐通道）：
+//!   随机选择一个通道按 σ̄ 的指数分布采样试探碰撞，以各通道 σ_t(p) / σ̄ 的平均值为概率接受为散射，
+//!   否则是一次空碰撞。散射、空碰撞与穿出界面时乘上对应的逐通道权重，使每个通道都是无偏的
+//! - 自由程是常数时 σ_t = σ̄，不会发生空碰撞；随位置变化越剧烈，空碰撞越多，
+//!   与 `ChromaticMedium` 一样每次空碰撞占用一次弹射次数
+//! - 射线是否在内部由边界上第一个交点的朝向判断，因此内部不能再放其他物体；
+//!   随机游走的每次散射占用一次弹射次数，反照率很高的物体需要相应地提高 `max_depth`
+
+use std::sync::Arc;
+
+use crate::constant_medium::boundary_segments;
+use crate::hittable::{HitRecord, Hittable, ShadingCache};
+use crate::interval::Interval;
+use crate::material::{Dielectric, Material, ScatterRecord};
+use crate::pdf::SpherePdf;
+use crate::ray::Ray;
+use crate::rtweekend::{self, random_double};
+use crate::texture::{SolidColor, Texture};
+use crate::vec3::{Color, Point3, Vec3};
+
+//  平均自由程的下限，避免为 0 时出现无穷大的消光系数
+const MIN_MEAN_FREE_PATH: f64 = 1e-6;
+
+fn average(c: &Color) -> f64 {
+    (c.x() + c.y() + c.z()) / 3.0
+}
+
+fn exp(c: &Color) -> Color {
+    Color::new(c.x().exp(), c.y().exp(), c.z().exp())
+}
+
+/// 内部介质的系数，以及自由程采样的权重
+#[derive(Debug)]
+struct Interior {
+    albedo: Arc<dyn Texture>,
+    mean_free_path: Arc<dyn Texture>,
+    sigma_bar: Color, //  主消光系数，σ_t 在任何位置都不超过它
+}
+
+impl Interior {
+    fn new(albedo: Arc<dyn Texture>, mean_free_path: Arc<dyn Texture>, min_mean_free_path: Color) -> Self {
+        let inverse = |d: f64| 1.0 / d.max(MIN_MEAN_FREE_PATH);
+        Self {
+            albedo,
+            mean_free_path,
+            sigma_bar: Color::new(
+                inverse(min_mean_free_path.x()),
+                inverse(min_mean_free_path.y()),
+                inverse(min_mean_free_path.z()),
+            ),
+        }
+    }
+
+    //  p 处的 σ_t / σ̄，比下限更短的自由程按下限计算
+    fn density_ratio(&self, p: &Point3) -> Color {
+        let mfp = self.mean_free_path.value(0.0, 0.0, p);
+        let ratio = |d: f64, bar: f64| (1.0 / (d.max(MIN_MEAN_FREE_PATH) * bar)).min(1.0);
+        Color::new(
+            ratio(mfp.x(), self.sigma_bar.x()),
+            ratio(mfp.y(), self.sigma_bar.y()),
+            ratio(mfp.z(), self.sigma_bar.z()),
+        )
+    }
+
+    //  p 处的试探碰撞被接受为散射的概率
+    fn scatter_probability(&self, p: &Point3) -> f64 {
+        average(&self.density_ratio(p))
+    }
+
+    //  走过 `distance` 后发生试探碰撞时的权重 σ̄·Tr / avg(σ̄·Tr)
+    fn collision_weight(&self, distance: f64) -> Color {
+        let sigma_bar = self.sigma_bar;
+        let density = sigma_bar * exp(&(-distance * sigma_bar));
+        let pdf = average(&density);
+        if pdf > 0.0 { density / pdf } else { Color::new(0.0, 0.0, 0.0) }
+    }
+
+    //  p 处的试探碰撞被接受为散射时的权重 (σ_t / σ̄) / P
+    fn scatter_weight(&self, p: &Point3) -> Color {
+        let ratio = self.density_ratio(p);
+        let probability = average(&ratio);
+        if probability > 0.0 { ratio / probability } else { Color::new(0.0, 0.0, 0.0) }
+    }
+
+    //  p 处的试探碰撞是空碰撞时的权重 (1 - σ_t / σ̄) / (1 - P)
+    fn null_weight(&self, p: &Point3) -> Color {
+        let ratio = Color::new(1.0, 1.0, 1.0) - self.density_ratio(p);
+        let probability = average(&ratio);
+        if probability > 0.0 { ratio / probability } else { Color::new(0.0, 0.0, 0.0) }
+    }
+
+    //  走过 `distance` 仍没有试探碰撞、到达界面时的权重 T̄r / avg(T̄r)
+    fn exit_weight(&self, distance: f64) -> Color {
+        let tr = exp(&(-distance * self.sigma_bar));
+        let probability = average(&tr);
+        if probability > 0.0 { tr / probability } else { Color::new(0.0, 0.0, 0.0) }
+    }
+}
+
+//  内部的散射点：各向同性相函数，自由程采样的权重并入 BSDF 的值中
+#[derive(Debug)]
+struct InteriorScatter {
+    interior: Arc<Interior>,
+}
+
+impl InteriorScatter {
+    fn weight(&self, r_in: &Ray, rec: &HitRecord) -> Color {
+        let distance = (rec.p - *r_in.origin()).length();
+        self.interior.collision_weight(distance)
+            * self.interior.scatter_weight(&rec.p)
+            * self.interior.albedo.value(0.0, 0.0, &rec.p)
+    }
+}
+
+impl Material for InteriorScatter {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        Some(ScatterRecord {
+            attenuation: self.weight(r_in, rec),
+            pdf_ptr: Some(Arc::new(SpherePdf::new())),
+            skip_pdf_ray: None,
+            null_collision: false,
+        })
+    }
+
+    fn pdf_probability(&self, _r_in: &Ray, _rec: &HitRecord) -> f64 {
+        1.0
+    }
+
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
+        1.0 / (4.0 * rtweekend::PI_F64)
+    }
+
+    fn scattering_value(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.weight(r_in, rec) * self.scattering_pdf(r_in, rec, scattered)
+    }
+
+    fn is_volumetric(&self) -> bool {
+        true
+    }
+}
+
+//  内部的空碰撞：沿原方向继续，只按通道调整权重
+#[derive(Debug)]
+struct InteriorNull {
+    interior: Arc<Interior>,
+}
+
+impl Material for InteriorNull {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let distance = (rec.p - *r_in.origin()).length();
+        Some(ScatterRecord {
+            attenuation: self.interior.collision_weight(distance) * self.interior.null_weight(&rec.p),
+            pdf_ptr: None,
+            skip_pdf_ray: Some(r_in.scattered(rec.p, *r_in.direction())),
+            null_collision: true,
+        })
+    }
+}
+
+//  边界：光滑电介质，从内部射到界面时乘上未发生试探碰撞的权重
+#[derive(Debug)]
+struct Boundary {
+    dielectric: Dielectric,
+    interior: Arc<Interior>,
+}
+
+impl Material for Boundary {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let mut srec = self.dielectric.scatter(r_in, rec)?;
+        if !rec.front_face {
+            let distance = (rec.p - *r_in.origin()).length();
+            srec.attenuation = srec.attenuation * self.interior.exit_weight(distance);
+        }
+        Some(srec)
+    }
+}
+
+pub struct Subsurface {
+    boundary: Arc<dyn Hittable>,
+    interior: Arc<Interior>,
+    surface: Boundary,
+    scatter: InteriorScatter,
+    null_collision: InteriorNull,
+}
+
+impl Subsurface {
+    /// 以 `boundary` 为外形、折射率为 `refraction_index` 的次表面散射物体，
+    /// `albedo` 为单次散射反照率，`mean_free_path` 为各通道的平均自由程（与场景同一长度单位）
+    pub fn new(boundary: Arc<dyn Hittable>, refraction_index: f64, albedo: Color, mean_free_path: Color) -> Self {
+        Self::from_texture(
+            boundary,
+            refraction_index,
+            Arc::new(SolidColor::new(albedo)),
+            Arc::new(SolidColor::new(mean_free_path)),
+            mean_free_path,
+        )
+    }
+
+    /// 反照率与平均自由程随位置变化的次表面散射物体，
+    /// `min_mean_free_path` 为 `mean_free_path` 各通道的下限，比它更短的自由程按下限计算；
+    /// 下限越接近实际的自由程，空碰撞越少
+    pub fn from_texture(
+        boundary: Arc<dyn Hittable>,
+        refraction_index: f64,
+        albedo: Arc<dyn Texture>,
+        mean_free_path: Arc<dyn Texture>,
+        min_mean_free_path: Color,
+    ) -> Self {
+        let interior = Arc::new(Interior::new(albedo, mean_free_path, min_mean_free_path));
+        Self {
+            boundary,
+            interior: interior.clone(),
+            surface: Boundary {
+                dielectric: Dielectric::new(refraction_index),
+                interior: interior.clone(),
+            },
+            scatter: InteriorScatter { interior: interior.clone() },
+            null_collision: InteriorNull { interior },
+        }
+    }
+}
+
+impl Hittable for Subsurface {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: &Interval) -> Option<HitRecord<'a>> {
+        let surface = self.boundary.hit(r, &Interval::new(ray_t.min, f64::INFINITY));
+
+        //  射线在内部：随机选一个通道采样试探碰撞，先于界面发生的是一次散射或空碰撞
+        if let Some(exit) = surface.as_ref().filter(|rec| !rec.front_face) {
+            let sigma_bar = self.interior.sigma_bar;
+            let channel = ((random_double() * 3.0) as usize).min(2);
+            let ray_length = r.direction().length();
+            let t = (-random_double().ln() / (sigma_bar[channel] * ray_length)).max(ray_t.min);
+            if t < exit.t {
+                if t >= ray_t.max {
+                    return None;
+                }
+                let p = r.at(t);
+                let mat: &dyn Material = if random_double() < self.interior.scatter_probability(&p) {
+                    &self.scatter
+                } else {
+                    &self.null_collision
+                };
+                return Some(HitRecord {
+                    t,
+                    p,
+                    normal: Vec3::new(1.0, 0.0, 0.0), // arbitrary
+                    front_face: true,                 // arbitrary
+                    mat,
+                    u: 0.0,
+                    v: 0.0,
+                    dpdu: Vec3::new(0.0, 0.0, 0.0), // 没有表面，也就没有切线
+                    dpdv: Vec3::new(0.0, 0.0, 0.0),
+                    shading: ShadingCache::default(),
+                    object: self as *const Self as usize,
+                });
+            }
+        }
+
+        let mut rec = surface.filter(|rec| rec.t < ray_t.max)?;
+        rec.mat = &self.surface;
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> crate::AABB::Aabb {
+        self.boundary.bounding_box()
+    }
+
+    //  电介质界面遮挡连接；整段都在内部时用 ratio tracking 估计，主密度取 σ̄ 中最大的通道
+    fn transmittance(&self, r: &Ray, ray_t: &Interval) -> Color {
+        if self.boundary.hit(r, ray_t).is_some() {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let sigma_max = self.interior.sigma_bar.max_component();
+        let ray_length = r.direction().length();
+
+        let mut tr = Color::new(1.0, 1.0, 1.0);
+        for segment in boundary_segments(&*self.boundary, r, ray_t) {
+            let mut t = segment.min;
+            loop {
+                t -= random_double().ln() / (sigma_max * ray_length);
+                if t >= segment.max {
+                    break;
+                }
+                let sigma_t = self.interior.density_ratio(&r.at(t)) * self.interior.sigma_bar;
+                tr = tr * (Color::new(1.0, 1.0, 1.0) - sigma_t / sigma_max);
+            }
+        }
+        tr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::EmptyMaterial;
+    use crate::sphere::Sphere;
+
+    //  x < 0 的一半平均自由程为 `near`，另一半为 `far`
+    #[derive(Debug)]
+    struct Halves {
+        near: Color,
+        far: Color,
+    }
+
+    impl Texture for Halves {
+        fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+            if p.x() < 0.0 { self.near } else { self.far }
+        }
+    }
+
+    fn object() -> Subsurface {
+        let boundary = Arc::new(Sphere::new_stationary(Point3::new(0.0, 0.0, 0.0), 10.0, Arc::new(EmptyMaterial)));
+        let mean_free_path = Arc::new(Halves {
+            near: Color::new(2.0, 4.0, 8.0),
+            far: Color::new(4.0, 8.0, 16.0),
+        });
+        let albedo = Arc::new(SolidColor::new(Color::new(1.0, 1.0, 1.0)));
+        Subsurface::from_texture(boundary, 1.5, albedo, mean_free_path, Color::new(2.0, 4.0, 8.0))
+    }
+
+    //  从内部沿 +y 出发，跟随空碰撞直到散射或到达界面；返回是否散射，以及穿出界面时的权重
+    fn walk(object: &Subsurface, x: f64) -> (bool, Color) {
+        let mut r = Ray::new(Point3::new(x, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 0.0);
+        let mut weight = Color::new(1.0, 1.0, 1.0);
+        loop {
+            //  空碰撞离界面不到 0.001 时找不到界面，剩下的距离可以忽略
+            let Some(rec) = object.hit(&r, &Interval::new(0.001, f64::INFINITY)) else {
+                return (false, weight);
+            };
+            if rec.mat.is_volumetric() {
+                return (true, Color::new(0.0, 0.0, 0.0));
+            }
+            let distance = (rec.p - *r.origin()).length();
+            let srec = rec.mat.scatter(&r, &rec).unwrap();
+            if !srec.null_collision {
+                return (false, weight * object.interior.exit_weight(distance));
+            }
+            weight = weight * srec.attenuation;
+            r = srec.skip_pdf_ray.unwrap();
+        }
+    }
+
+    #[test]
+    fn spatially_varying_mean_free_path_changes_the_walk() {
+        let object = object();
+        let n = 40000;
+        //  两条路径在内部都走过 d = √75
+        let d = 75.0f64.sqrt();
+        let mut fractions = Vec::new();
+        for (x, mfp) in [(-5.0, Color::new(2.0, 4.0, 8.0)), (5.0, Color::new(4.0, 8.0, 16.0))] {
+            let mut scattered = 0;
+            let mut tr = Color::new(0.0, 0.0, 0.0);
+            for _ in 0..n {
+                let (hit, weight) = walk(&object, x);
+                scattered += hit as usize;
+                tr += weight;
+            }
+            tr /= n as f64;
+            fractions.push(scattered as f64 / n as f64);
+
+            //  各通道穿出的权重之和是 exp(-d / 平均自由程) 的无偏估计
+            for i in 0..3 {
+                let expected = (-d / mfp[i]).exp();
+                assert!((tr[i] - expected).abs() < 0.02, "x = {}: {:?} vs {}", x, tr, expected);
+            }
+        }
+        //  自由程短的一半更多地在穿出之前散射
+        assert!(fractions[0] > fractions[1] + 0.05, "{:?}", fractions);
+    }
+
+    #[test]
+    fn transmittance_inside_follows_the_local_mean_free_path() {
+        let object = object();
+        let n = 20000;
+        for (x, mfp) in [(-5.0, Color::new(2.0, 4.0, 8.0)), (5.0, Color::new(4.0, 8.0, 16.0))] {
+            let r = Ray::new(Point3::new(x, -4.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 0.0);
+            let mut tr = Color::new(0.0, 0.0, 0.0);
+            for _ in 0..n {
+                tr += object.transmittance(&r, &Interval::new(0.0, 8.0));
+            }
+            tr /= n as f64;
+            for i in 0..3 {
+                let expected = (-8.0 / mfp[i]).exp();
+                assert!((tr[i] - expected).abs() < 0.02, "x = {}: {:?} vs {}", x, tr, expected);
+            }
+        }
+    }
+}
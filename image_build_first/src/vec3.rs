@@ -251,24 +251,17 @@ impl Color {
     }
 
     pub fn write_color<W: Write>(writer: &mut W, pixel_color: &Color) -> Result<()> {
-        let mut r = pixel_color.x();
-        let mut g = pixel_color.y();
-        let mut b = pixel_color.z();
-
-        r = if r.is_nan() { 0.0 } else { r };
-        g = if g.is_nan() { 0.0 } else { g };
-        b = if b.is_nan() { 0.0 } else { b };
-
-        r = Color::double_linear_to_gamma(r);
-        g = Color::double_linear_to_gamma(g);
-        b = Color::double_linear_to_gamma(b);
+        let [ir, ig, ib] = Color::to_rgb8(pixel_color);
+        writeln!(writer, "{} {} {}", ir, ig, ib)
+    }
 
+    /// 线性颜色经伽马校正后量化为 8 位，NaN 按 0 处理
+    pub fn to_rgb8(pixel_color: &Color) -> [u8; 3] {
         let intensity = Interval::new(0.0, 0.999);
-
-        let ir = (256.0 * intensity.clamp(r)) as u8;
-        let ig = (256.0 * intensity.clamp(g)) as u8;
-        let ib = (256.0 * intensity.clamp(b)) as u8;
-
-        writeln!(writer, "{} {} {}", ir, ig, ib)
+        let quantize = |c: f64| {
+            let c = if c.is_nan() { 0.0 } else { c };
+            (256.0 * intensity.clamp(Color::double_linear_to_gamma(c))) as u8
+        };
+        [quantize(pixel_color.x()), quantize(pixel_color.y()), quantize(pixel_color.z())]
     }
 }